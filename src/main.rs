@@ -1,15 +1,46 @@
 use chrono::prelude::*;
-use leptess::tesseract;
 use opencv::{
     core::{self, VecN},
     highgui, imgcodecs,
     imgproc::{self, LINE_8},
     prelude::*,
 };
-use std::{ffi::CString, fs, path::Path};
-use types::tag::{self, image_to_tags};
+use std::{fs, path::Path, sync::Arc};
+#[cfg(feature = "onnx")]
+use types::onnx::OnnxClassifier;
+#[cfg(feature = "tesseract")]
+use types::tesseract::{TesseractBackend, Vocabularies};
+use types::{
+    classifier::{self, TemplateClassifier},
+    config::Config,
+    language::ServerLanguage,
+    matcher::{self, MatcherScore},
+    ocr::{self, OcrBackend, OcrEngine},
+    pool::OcrPool,
+    scan::{self, ScanResult},
+    tag::{self, image_to_tags},
+    template,
+    video::{self, FrameScan},
+};
 mod types;
 
+static USAGE: &str = "\
+Usage: retag [--config FILE] [--backend ENGINE] [--matcher MATCHER] [--threads N] [--photo] [--sample-rate FPS] [--debug DIR] [--lang LANG] [--server SERVER] [--detect-server] INPUT...\n\
+Usage: retag [--config FILE] [--photo] build-tag-library LABELS\n\
+Usage: retag [--config FILE] [--backend ENGINE] [--photo] evaluate LABELS\n\
+Usage: retag capture-template NAME SCREENSHOT X Y WIDTH HEIGHT\n\n\
+INPUT is an image, a video file or a numbered frame sequence such as frames/%04d.png.\n\
+LANG is the language tags are printed in, en, cn, jp or kr. SERVER takes the same values and\n\
+applies to the inputs that follow it, so one run can scan screenshots of several servers.\n\
+Inputs before the first --server use server_language. --detect-server detects the server of\n\
+every tag selection screenshot instead, for folders that mix servers.\n\
+ENGINE is tesseract, templates or onnx. MATCHER is difflib or confusion. build-tag-library adds\n\
+the tags of the labeled screenshots listed in LABELS to the tag library of the templates engine.\n\
+evaluate reports how many of the labeled English tags each matcher recognizes. capture-template\n\
+stores the given pixel region of SCREENSHOT as the template NAME, e.g. recruitment_permit.png\n\
+or expedited_plan.png for the resource counters, screen_tag_selection.png, screen_overview.png\n\
+and screen_result.png for screen detection, or star.png for the rarity on the result screen.";
+
 static ROI_VERTICAL: (f64, f64) = (
     0.45, // ignore top 45%
     0.30, // ignore bottom 30%
@@ -50,8 +81,260 @@ fn draw_boxes(
     Ok(())
 }
 
-fn main() {}
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let mut config_path: Option<String> = None;
+    let mut overrides: Vec<(&str, String)> = vec![];
+    let mut inputs: Vec<String> = vec![];
+    let mut languages: Vec<Option<ServerLanguage>> = vec![];
+    let mut language: Option<ServerLanguage> = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = Some(args.next().ok_or(USAGE)?),
+            "--backend" => overrides.push(("ocr_backend", args.next().ok_or(USAGE)?)),
+            "--matcher" => overrides.push(("matcher", args.next().ok_or(USAGE)?)),
+            "--threads" => overrides.push(("threads", args.next().ok_or(USAGE)?)),
+            "--photo" => overrides.push(("photo_input", "true".into())),
+            "--debug" => overrides.push(("debug_dir", args.next().ok_or(USAGE)?)),
+            "--sample-rate" => overrides.push(("video_sample_rate", args.next().ok_or(USAGE)?)),
+            "--detect-server" => overrides.push(("detect_languages", "en, cn, jp, kr".into())),
+            "--lang" => overrides.push(("display_language", args.next().ok_or(USAGE)?)),
+            "--server" => language = Some(args.next().ok_or(USAGE)?.parse::<ServerLanguage>()?),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => {
+                inputs.push(arg);
+                languages.push(language);
+            }
+        }
+    }
+    if inputs.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let mut config: Config = match config_path {
+        Some(path) => Config::load(Path::new(&path))?,
+        None => Config::default(),
+    };
+    for (key, value) in overrides {
+        config.set(key, &value)?;
+    }
+
+    if inputs[0] == "build-tag-library" {
+        let [_, labels] = inputs.as_slice() else {
+            return Err(USAGE.into());
+        };
+        let written: usize =
+            classifier::build_library(Path::new(labels), Path::new(&config.tag_library), &config)?;
+        println!(
+            "{} reference crops written to {}",
+            written, config.tag_library
+        );
+        return Ok(());
+    }
+
+    if inputs[0] == "capture-template" {
+        let [_, name, screenshot, x, y, width, height] = inputs.as_slice() else {
+            return Err(USAGE.into());
+        };
+        let image: Mat = imgcodecs::imread(screenshot, imgcodecs::IMREAD_COLOR_BGR)?;
+        if image.empty() {
+            return Err(format!("{}: could not read image", screenshot).into());
+        }
+        let rect: core::Rect = core::Rect::new(
+            x.parse::<i32>()?,
+            y.parse::<i32>()?,
+            width.parse::<i32>()?,
+            height.parse::<i32>()?,
+        );
+        let path: std::path::PathBuf = template::capture_template(&image, rect, name)?;
+        println!("Template written to {}", path.display());
+        return Ok(());
+    }
+
+    if inputs[0] == "evaluate" {
+        let [_, labels] = inputs.as_slice() else {
+            return Err(USAGE.into());
+        };
+        let mut backend: Box<dyn OcrBackend> = backend_factory(Arc::new(config.clone()))?()?;
+        backend.set_whitelist(&config.server_language.tag_whitelist())?;
+        let scores: Vec<MatcherScore> =
+            matcher::evaluate(Path::new(labels), backend.as_mut(), &config)?;
+        for score in scores {
+            println!("{}", score);
+        }
+        return Ok(());
+    }
+
+    let config: Arc<Config> = Arc::new(config);
+    let mut pool: OcrPool = OcrPool::new(config.threads, backend_factory(Arc::clone(&config))?)?;
+    let mut images: Vec<String> = vec![];
+    let mut images_config: Arc<Config> = Arc::clone(&config);
+    for (input, language) in inputs.into_iter().zip(languages) {
+        // Images are batched until the server changes, then scanned with their own settings.
+        let language: ServerLanguage = language.unwrap_or(config.server_language);
+        if language != images_config.server_language {
+            scan_images(&mut pool, std::mem::take(&mut images), &images_config)?;
+            images_config = if language == config.server_language {
+                Arc::clone(&config)
+            } else {
+                Arc::new(Config {
+                    server_language: language,
+                    ..Config::clone(&config)
+                })
+            };
+        }
+        if video::is_frame_source(&input) {
+            scan_images(&mut pool, std::mem::take(&mut images), &images_config)?;
+            video::scan_frames(&input, &mut pool, &images_config, |scan: FrameScan| {
+                let position: String = match scan.timestamp {
+                    Some(t) => format!(
+                        "{:02}:{:02}:{:02}",
+                        t.num_hours(),
+                        t.num_minutes() % 60,
+                        t.num_seconds() % 60
+                    ),
+                    None => format!("frame {}", scan.frame_index),
+                };
+                println!("{} @ {}\n{}", input, position, scan.result);
+            })?;
+        } else {
+            images.push(input);
+        }
+    }
+    scan_images(&mut pool, images, &images_config)?;
+    if let Some(cache) = &config.tag_cache {
+        cache.save()?;
+        println!("{}", cache.stats());
+    }
+    Ok(())
+}
+
+/// Scans a batch of images and prints the results in input order.
+///
+/// A batch with at least one image per worker is spread over the workers, one image per job.
+/// Smaller batches, such as a single screenshot, are scanned one image at a time with the
+/// pool itself as the backend, so the tag crops of each image are read in parallel instead.
+/// An image that cannot be read or scanned is reported and the batch goes on.
+///
+/// # Errors
+/// Returns an error if the pool is shut down.
+fn scan_images(
+    pool: &mut OcrPool,
+    inputs: Vec<String>,
+    config: &Arc<Config>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let results: Vec<Result<Option<ScanResult>, String>> = if inputs.len() < config.threads {
+        inputs
+            .iter()
+            .map(|input: &String| scan_input(input, pool, config))
+            .collect()
+    } else {
+        let job_config: Arc<Config> = Arc::clone(config);
+        pool.map(
+            inputs.clone(),
+            move |backend: &mut dyn OcrBackend, input: String| {
+                scan_input(&input, backend, &job_config)
+            },
+        )?
+    };
+    for (input, result) in inputs.iter().zip(results) {
+        match result {
+            Ok(Some(result)) => println!("{}\n{}", input, result),
+            Ok(None) => eprintln!("{}: could not read image", input),
+            Err(e) => eprintln!("{}: {}", input, e),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the image at `input` and scans it with `backend`.
+///
+/// # Returns
+/// - `Result<Option<ScanResult>, String>`: The scan result, or `None` if the file is not an
+///   image. Errors are returned as strings so they can be sent back from pool workers.
+fn scan_input(
+    input: &str,
+    backend: &mut dyn OcrBackend,
+    config: &Config,
+) -> Result<Option<ScanResult>, String> {
+    let image: Mat = imgcodecs::imread(input, imgcodecs::IMREAD_COLOR_BGR)
+        .map_err(|e: opencv::Error| e.to_string())?;
+    if image.empty() {
+        return Ok(None);
+    }
+    scan::scan_image(&image, backend, config)
+        .map(Some)
+        .map_err(|e: Box<dyn std::error::Error>| e.to_string())
+}
+
+/// Creates backends of the engine selected by `ocr_backend`, e.g. one per pool worker.
+type BackendFactory =
+    Box<dyn Fn() -> Result<Box<dyn OcrBackend>, Box<dyn std::error::Error>> + Send + Sync>;
+
+/// Returns a factory for the recognition backend selected by `ocr_backend`.
+///
+/// Everything the backends share is prepared here, once, before any backend exists. For
+/// Tesseract with `tess_user_words`, the vocabularies of every language are written to a
+/// directory private to this process, which is removed once the last backend is dropped.
+///
+/// # Errors
+/// Returns an error if the vocabularies cannot be written, or if an engine is selected whose
+/// cargo feature was not enabled at build time. Errors initializing a backend are returned
+/// by the factory.
+fn backend_factory(config: Arc<Config>) -> Result<BackendFactory, Box<dyn std::error::Error>> {
+    match config.ocr_backend {
+        #[cfg(feature = "tesseract")]
+        OcrEngine::Tesseract => {
+            let vocabularies: Option<Arc<Vocabularies>> = if config.tess_user_words {
+                let dir: std::path::PathBuf =
+                    std::env::temp_dir().join(format!("retag-{}", std::process::id()));
+                Some(Arc::new(Vocabularies::write(&dir)?))
+            } else {
+                None
+            };
+            Ok(Box::new(
+                move || -> Result<Box<dyn OcrBackend>, Box<dyn std::error::Error>> {
+                    Ok(Box::new(TesseractBackend::with_options(
+                        &config.tessdata_dir,
+                        config.server_language,
+                        config.tess_engine_mode,
+                        vocabularies.clone(),
+                        &config.tess_variables,
+                    )?))
+                },
+            ))
+        }
+        #[cfg(not(feature = "tesseract"))]
+        OcrEngine::Tesseract => Err("retag was built without the `tesseract` feature".into()),
+        OcrEngine::Templates => Ok(Box::new(
+            move || -> Result<Box<dyn OcrBackend>, Box<dyn std::error::Error>> {
+                Ok(Box::new(TemplateClassifier::load(Path::new(
+                    &config.tag_library,
+                ))?))
+            },
+        )),
+        #[cfg(feature = "onnx")]
+        OcrEngine::Onnx => Ok(Box::new(
+            move || -> Result<Box<dyn OcrBackend>, Box<dyn std::error::Error>> {
+                Ok(Box::new(OnnxClassifier::load(Path::new(&config.tag_model))?))
+            },
+        )),
+        #[cfg(not(feature = "onnx"))]
+        OcrEngine::Onnx => Err("retag was built without the `onnx` feature".into()),
+    }
+}
 
+#[cfg(feature = "tesseract")]
 #[test]
 fn main_test() -> Result<(), Box<dyn std::error::Error>> {
     let path: &Path = Path::new("images/test/");
@@ -70,11 +353,9 @@ fn main_test() -> Result<(), Box<dyn std::error::Error>> {
         })
         .collect();
 
-    let mut tess: tesseract::TessApi = tesseract::TessApi::new(Some("/usr/share/tessdata"), "eng")?; //Tesseract::new(Some("path/to/tessdata"), Some("eng")).unwrap();
-    let key_cstr: CString = CString::new("tessedit_char_whitelist").expect("CString::new failed");
-    let value_cstr: CString = CString::new("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ-")
-        .expect("CString::new failed");
-    tess.raw.set_variable(&key_cstr, &value_cstr)?;
+    let mut backend: TesseractBackend =
+        TesseractBackend::new("/usr/share/tessdata", ServerLanguage::English)?;
+    backend.set_whitelist(ocr::TAG_WHITELIST)?;
 
     let start: DateTime<Utc> = Utc::now();
     let mut images: Vec<Mat> = vec![];
@@ -96,7 +377,7 @@ fn main_test() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut rec_tags: Vec<Vec<tag::Tag>> = vec![];
     for image in &images {
-        let tags: Vec<tag::Tag> = image_to_tags(&image, &mut tess)?;
+        let tags: Vec<tag::Tag> = image_to_tags(&image, &mut backend, &Config::default())?;
         rec_tags.push(tags);
         // println!("{:?}", texts);
 
@@ -0,0 +1,106 @@
+/// Assigns every row of a similarity matrix to a distinct column so that the total similarity
+/// is maximal.
+///
+/// This is the Hungarian algorithm on the negated similarities, with row and column potentials
+/// so that it runs in `O(rows² · columns)`.
+///
+/// # Parameters
+/// - `similarity`: One row per item to assign and one column per possible value. All rows must
+///   have the same length.
+///
+/// # Returns
+/// - `Option<(Vec<usize>, f64)>`: The column assigned to each row and the total similarity of
+///   the assignment, or `None` if there are more rows than columns and no assignment without
+///   duplicates exists.
+///
+/// # Example Usage
+/// ```rust
+/// let similarity: Vec<Vec<f64>> = vec![vec![0.9, 0.8], vec![0.95, 0.3]];
+/// let (columns, total) = assign(&similarity).unwrap();
+/// assert_eq!(columns, vec![1, 0]);
+/// ```
+pub(crate) fn assign(similarity: &[Vec<f64>]) -> Option<(Vec<usize>, f64)> {
+    let rows: usize = similarity.len();
+    let columns: usize = similarity.first().map_or(0, Vec::len);
+    if rows > columns {
+        return None;
+    }
+
+    // Potentials and matching are 1-indexed; column 0 is a virtual start column.
+    let mut u: Vec<f64> = vec![0.0; rows + 1];
+    let mut v: Vec<f64> = vec![0.0; columns + 1];
+    let mut matched_row: Vec<usize> = vec![0; columns + 1];
+    let mut way: Vec<usize> = vec![0; columns + 1];
+    for row in 1..=rows {
+        matched_row[0] = row;
+        let mut j0: usize = 0;
+        let mut min_slack: Vec<f64> = vec![f64::INFINITY; columns + 1];
+        let mut used: Vec<bool> = vec![false; columns + 1];
+        loop {
+            used[j0] = true;
+            let i0: usize = matched_row[j0];
+            let mut delta: f64 = f64::INFINITY;
+            let mut j1: usize = 0;
+            for j in 1..=columns {
+                if used[j] {
+                    continue;
+                }
+                let slack: f64 = -similarity[i0 - 1][j - 1] - u[i0] - v[j];
+                if slack < min_slack[j] {
+                    min_slack[j] = slack;
+                    way[j] = j0;
+                }
+                if min_slack[j] < delta {
+                    delta = min_slack[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=columns {
+                if used[j] {
+                    u[matched_row[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_slack[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if matched_row[j0] == 0 {
+                break;
+            }
+        }
+        loop {
+            let j1: usize = way[j0];
+            matched_row[j0] = matched_row[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut assigned: Vec<usize> = vec![0; rows];
+    for j in 1..=columns {
+        if matched_row[j] != 0 {
+            assigned[matched_row[j] - 1] = j - 1;
+        }
+    }
+    let total: f64 = assigned
+        .iter()
+        .enumerate()
+        .map(|(row, column): (usize, &usize)| similarity[row][*column])
+        .sum();
+    Some((assigned, total))
+}
+
+#[test]
+fn assign_avoids_duplicate_columns() {
+    let similarity: Vec<Vec<f64>> = vec![
+        vec![0.9, 0.8, 0.1],
+        vec![0.95, 0.3, 0.2],
+        vec![0.2, 0.1, 0.4],
+    ];
+    let (columns, total) = assign(&similarity).unwrap();
+    assert_eq!(columns, vec![1, 0, 2]);
+    assert!((total - 2.15).abs() < 1e-9);
+    assert!(assign(&[vec![1.0], vec![1.0]]).is_none());
+}
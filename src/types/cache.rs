@@ -0,0 +1,275 @@
+use super::tag::TagType;
+use opencv::{
+    core::{self, Mat, Size, CV_32F},
+    imgproc,
+    prelude::*,
+};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// Side length of the image the DCT of the perceptual hash is computed on.
+static HASH_IMAGE_SIZE: i32 = 32;
+/// Side length of the block of low DCT frequencies that make up the 64 hash bits.
+static HASH_BLOCK_SIZE: i32 = 8;
+
+/// A cached recognition result.
+///
+/// # Fields
+/// - `tag_type`: The tag the crop was recognized as.
+/// - `confidence`: The OCR confidence of the reading the tag was matched from.
+/// - `match_ratio`: The similarity between that reading and the tag string.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheEntry {
+    pub tag_type: TagType,
+    pub confidence: f64,
+    pub match_ratio: f64,
+}
+
+/// Hit and miss counts of a `TagCache`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lookups: usize = self.hits + self.misses;
+        let rate: f64 = if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64 * 100.0
+        };
+        write!(
+            f,
+            "Tag cache: {} hits, {} misses ({:.0}% hit rate), {} entries",
+            self.hits, self.misses, rate, self.entries
+        )
+    }
+}
+
+/// Remembers which tag a tag crop was recognized as, keyed by the perceptual hash of the
+/// preprocessed crop.
+///
+/// In videos the same tag buttons are shown for many frames, and across sessions the same
+/// buttons recur. Crops found in the cache skip OCR entirely. The cache is shared between the
+/// OCR workers and can be persisted to a file, which holds one `hash tag confidence ratio`
+/// line per entry.
+#[derive(Debug, Default)]
+pub struct TagCache {
+    entries: Mutex<HashMap<u64, CacheEntry>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    file: Option<PathBuf>,
+}
+
+impl TagCache {
+    /// Opens a cache persisted in `path`. A missing file yields an empty cache that is
+    /// written to `path` by `TagCache::save`.
+    ///
+    /// # Errors
+    /// Returns an error if the file exists but cannot be read or a line is malformed.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut entries: HashMap<u64, CacheEntry> = HashMap::new();
+        if path.exists() {
+            for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let [hash, tag, confidence, match_ratio] = fields.as_slice() else {
+                    return Err(format!(
+                        "line {} is not a `hash tag confidence ratio` entry",
+                        index + 1
+                    )
+                    .into());
+                };
+                entries.insert(
+                    u64::from_str_radix(hash, 16)?,
+                    CacheEntry {
+                        tag_type: tag.parse::<TagType>()?,
+                        confidence: confidence.parse::<f64>()?,
+                        match_ratio: match_ratio.parse::<f64>()?,
+                    },
+                );
+            }
+        }
+        Ok(TagCache {
+            entries: Mutex::new(entries),
+            file: Some(path.to_path_buf()),
+            ..TagCache::default()
+        })
+    }
+
+    /// Looks up a crop by its perceptual hash and counts the lookup as a hit or a miss.
+    pub fn get(&self, key: u64) -> Option<CacheEntry> {
+        let entry: Option<CacheEntry> = self.entries.lock().unwrap().get(&key).copied();
+        let counter: &AtomicUsize = if entry.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry
+    }
+
+    /// Stores the recognition result of a crop.
+    pub fn insert(&self, key: u64, entry: CacheEntry) {
+        self.entries.lock().unwrap().insert(key, entry);
+    }
+
+    /// Returns the hit and miss counts since the cache was created.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+
+    /// Writes the cache to the file it was loaded from. Caches without a file are not saved.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut lines: Vec<String> = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(hash, entry): (&u64, &CacheEntry)| {
+                format!(
+                    "{:016x} {} {:.3} {:.3}",
+                    hash, entry.tag_type, entry.confidence, entry.match_ratio
+                )
+            })
+            .collect();
+        lines.sort();
+        fs::write(file, lines.join("\n"))?;
+        Ok(())
+    }
+}
+
+/// Computes the 64-bit perceptual hash of a single channel crop.
+///
+/// # Processing Steps
+/// 1. **Normalization**: The crop is resized to `HASH_IMAGE_SIZE` squared, which removes
+///    differences in scale and fine noise.
+///
+/// 2. **Frequency Transform**: A discrete cosine transform is applied and the
+///    `HASH_BLOCK_SIZE` squared lowest frequencies are kept. They describe the coarse
+///    structure of the crop, i.e. the shape of the label.
+///
+/// 3. **Bits**: Every frequency above the median of the block sets one bit. The DC term only
+///    reflects the overall brightness and is left out of the median.
+///
+/// # Errors
+/// Returns an error if the crop cannot be resized or transformed.
+pub fn perceptual_hash(crop: &Mat) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut small: Mat = Mat::default();
+    imgproc::resize(
+        crop,
+        &mut small,
+        Size::new(HASH_IMAGE_SIZE, HASH_IMAGE_SIZE),
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )?;
+    let mut float: Mat = Mat::default();
+    small.convert_to(&mut float, CV_32F, 1.0, 0.0)?;
+    let mut frequencies: Mat = Mat::default();
+    core::dct(&float, &mut frequencies, 0)?;
+
+    let mut block: Vec<f32> = Vec::with_capacity((HASH_BLOCK_SIZE * HASH_BLOCK_SIZE) as usize);
+    for y in 0..HASH_BLOCK_SIZE {
+        for x in 0..HASH_BLOCK_SIZE {
+            block.push(*frequencies.at_2d::<f32>(y, x)?);
+        }
+    }
+    let mut sorted: Vec<f32> = block[1..].to_vec();
+    sorted.sort_by(|a: &f32, b: &f32| a.total_cmp(b));
+    let median: f32 = sorted[sorted.len() / 2];
+    Ok(block
+        .iter()
+        .enumerate()
+        .filter(|(_, value): &(usize, &f32)| **value > median)
+        .fold(0u64, |hash: u64, (bit, _): (usize, &f32)| hash | 1 << bit))
+}
+
+#[cfg(test)]
+fn hash_fixture(
+    width: i32,
+    height: i32,
+    brightness: f64,
+) -> Result<Mat, Box<dyn std::error::Error>> {
+    let mut crop: Mat =
+        Mat::new_rows_cols_with_default(height, width, core::CV_8UC1, core::Scalar::all(0.0))?;
+    // A synthetic label: three bars of different widths, scaled with the crop.
+    for (x, w) in [(0.1, 0.15), (0.35, 0.3), (0.75, 0.1)] {
+        imgproc::rectangle(
+            &mut crop,
+            core::Rect::new(
+                (width as f64 * x) as i32,
+                height / 4,
+                (width as f64 * w) as i32,
+                height / 2,
+            ),
+            core::Scalar::all(brightness),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+    }
+    Ok(crop)
+}
+
+#[test]
+fn perceptual_hash_is_stable_under_scale_and_brightness() -> Result<(), Box<dyn std::error::Error>>
+{
+    let hash: u64 = perceptual_hash(&hash_fixture(160, 40, 255.0)?)?;
+    assert_eq!(perceptual_hash(&hash_fixture(160, 40, 255.0)?)?, hash);
+    assert!((perceptual_hash(&hash_fixture(320, 80, 200.0)?)? ^ hash).count_ones() <= 4);
+
+    let mut flipped: Mat = Mat::default();
+    core::flip(&hash_fixture(160, 40, 255.0)?, &mut flipped, 1)?;
+    assert!((perceptual_hash(&flipped)? ^ hash).count_ones() > 4);
+    Ok(())
+}
+
+#[test]
+fn tag_cache_survives_save_and_load() -> Result<(), Box<dyn std::error::Error>> {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("retag-tag-cache-{}.txt", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let cache: TagCache = TagCache::load(&path)?;
+    let entry: CacheEntry = CacheEntry {
+        tag_type: TagType::TopOperator,
+        confidence: 0.875,
+        match_ratio: 0.923,
+    };
+    cache.insert(0x0123_4567_89ab_cdef, entry);
+    cache.save()?;
+    let loaded: Result<TagCache, Box<dyn std::error::Error>> = TagCache::load(&path);
+    fs::remove_file(&path)?;
+
+    let loaded: TagCache = loaded?;
+    assert_eq!(loaded.get(0x0123_4567_89ab_cdef), Some(entry));
+    assert_eq!(loaded.get(0), None);
+    assert_eq!(
+        loaded.stats(),
+        CacheStats {
+            hits: 1,
+            misses: 1,
+            entries: 1
+        }
+    );
+    Ok(())
+}
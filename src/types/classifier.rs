@@ -0,0 +1,343 @@
+use super::{
+    config::Config,
+    language::ServerLanguage,
+    ocr::{self, OcrBackend, OcrCandidate},
+    perspective,
+    tag::{self, TAGS_STRINGS},
+    template,
+};
+use opencv::{
+    core::{self, Mat, Rect, Scalar, Size},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Size every tag crop and reference crop is normalized to before comparison.
+static CLASSIFIER_SIZE: (i32, i32) = (128, 32);
+/// Pixels the crop is padded by on every side, so references are matched with this much
+/// misalignment tolerance.
+static ALIGN_MARGIN: i32 = 4;
+/// Minimum normalized cross-correlation for a tag to be reported as a candidate.
+static TEMPLATE_ACCEPT_THRESH: f64 = 0.5;
+static MAX_CANDIDATES: usize = 3;
+
+/// A tag recognizer that compares tag crops against labeled reference crops instead of
+/// reading text.
+///
+/// There are only 28 tag labels, so a crop can be classified against known examples of each
+/// label instead of being read letter by letter, which avoids misreads. The library is a
+/// directory with one subdirectory per tag, named like the entries of `TAGS_STRINGS` (e.g.
+/// `Top-Operator/`), holding grayscale reference crops as written by `build_library`. Matches
+/// are reported as the tag strings of the language set with `set_language`, so the library
+/// has to be built from screenshots of that server.
+///
+/// Only tag crops can be classified. While the active whitelist is not a tag whitelist (see
+/// `ocr::is_tag_whitelist`), `recognize` returns no candidates, so the timer, counter, slot and
+/// name readers find nothing instead of a tag name.
+pub struct TemplateClassifier {
+    references: Vec<(usize, Vec<Mat>)>,
+    reading_tags: bool,
+    language: ServerLanguage,
+}
+
+impl TemplateClassifier {
+    /// Loads the reference crops of every tag from a library directory.
+    ///
+    /// Tags without a subdirectory are never reported.
+    ///
+    /// # Errors
+    /// Returns an error if a reference cannot be read or the library holds no references at
+    /// all.
+    pub fn load(library: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut references: Vec<(usize, Vec<Mat>)> = vec![];
+        for (index, name) in TAGS_STRINGS.iter().enumerate() {
+            let dir: PathBuf = library.join(name);
+            if !dir.is_dir() {
+                continue;
+            }
+            let mut crops: Vec<Mat> = vec![];
+            for entry in fs::read_dir(&dir)? {
+                let path: PathBuf = entry?.path();
+                let crop: Mat =
+                    imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_GRAYSCALE)?;
+                if crop.empty() {
+                    continue;
+                }
+                crops.push(normalize_crop(&crop)?);
+            }
+            if !crops.is_empty() {
+                references.push((index, crops));
+            }
+        }
+        if references.is_empty() {
+            return Err(
+                format!("tag library {} holds no reference crops", library.display()).into(),
+            );
+        }
+        Ok(TemplateClassifier {
+            references,
+            reading_tags: false,
+            language: ServerLanguage::default(),
+        })
+    }
+}
+
+impl OcrBackend for TemplateClassifier {
+    /// Remembers whether the whitelist is a tag whitelist.
+    fn set_whitelist(&mut self, chars: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.reading_tags = ocr::is_tag_whitelist(chars);
+        Ok(())
+    }
+
+    /// Remembers the language whose tag strings are reported.
+    fn set_language(&mut self, language: ServerLanguage) -> Result<(), Box<dyn std::error::Error>> {
+        self.language = language;
+        Ok(())
+    }
+
+    /// Classifies a preprocessed tag crop.
+    ///
+    /// Returns no candidates unless the active whitelist is a tag whitelist.
+    ///
+    /// The crop is normalized to `CLASSIFIER_SIZE` and padded by `ALIGN_MARGIN`. Every
+    /// reference is slid over the padded crop and the best normalized cross-correlation per
+    /// tag is its score. The tags scoring at least `TEMPLATE_ACCEPT_THRESH` are returned as
+    /// tag strings of the active language with their score as confidence, best first.
+    fn recognize(
+        &mut self,
+        grayscale: &Mat,
+    ) -> Result<Vec<OcrCandidate>, Box<dyn std::error::Error>> {
+        if !self.reading_tags {
+            return Ok(vec![]);
+        }
+        let normalized: Mat = normalize_crop(grayscale)?;
+        let mut padded: Mat = Mat::default();
+        core::copy_make_border(
+            &normalized,
+            &mut padded,
+            ALIGN_MARGIN,
+            ALIGN_MARGIN,
+            ALIGN_MARGIN,
+            ALIGN_MARGIN,
+            core::BORDER_REPLICATE,
+            Scalar::default(),
+        )?;
+
+        let mut candidates: Vec<OcrCandidate> = vec![];
+        for (index, crops) in &self.references {
+            let mut score: f64 = f64::MIN;
+            for reference in crops {
+                if let Some((_, s)) = template::best_match(&padded, reference)? {
+                    score = score.max(s);
+                }
+            }
+            if score >= TEMPLATE_ACCEPT_THRESH {
+                candidates.push(OcrCandidate {
+                    text: self.language.tag_strings()[*index].to_string(),
+                    confidence: score,
+                });
+            }
+        }
+        candidates
+            .sort_by(|a: &OcrCandidate, b: &OcrCandidate| b.confidence.total_cmp(&a.confidence));
+        candidates.truncate(MAX_CANDIDATES);
+        Ok(candidates)
+    }
+}
+
+/// A screenshot of a labeled set with its tag boxes paired with their labels.
+///
+/// # Fields
+/// - `path`: The file the screenshot was read from.
+/// - `gray`: The preprocessed grayscale tag panel, as produced by `tag::preprocess_panel`.
+/// - `tags`: The tag boxes inside `gray` in reading order, each with its tag string.
+pub(crate) struct LabeledScreenshot {
+    pub path: PathBuf,
+    pub gray: Mat,
+    pub tags: Vec<(Rect, &'static str)>,
+}
+
+/// Reads a labeled set of screenshots of the tag selection screen.
+///
+/// # Parameters
+/// - `labels`: A file with one line per screenshot of the form
+///   `path = Tag, Tag, Tag, Tag, Tag`. The tags are named like the entries of `TAGS_STRINGS`
+///   and listed in reading order: the top row from left to right, then the bottom row. Paths
+///   are relative to the labels file. Empty lines and lines starting with `#` are ignored.
+/// - `config`: The active settings. Photos are rectified if `photo_input` is set, and the
+///   panel goes through the same preprocessing as during a scan.
+///
+/// # Processing Steps
+/// 1. **Tag Detection**: Every screenshot is cropped to the tag panel and its tag boxes are
+///    detected exactly like in `tag::image_to_tags`.
+///
+/// 2. **Labeling**: The boxes are sorted into reading order and paired with the labels. A
+///    screenshot whose box count differs from its label count is rejected, because the
+///    pairing would be ambiguous.
+///
+/// # Errors
+/// Returns an error if the labels file is malformed, names an unknown tag, a screenshot
+/// cannot be read, or its tag boxes do not match its labels.
+pub(crate) fn read_labeled_set(
+    labels: &Path,
+    config: &Config,
+) -> Result<Vec<LabeledScreenshot>, Box<dyn std::error::Error>> {
+    let base: &Path = labels.parent().unwrap_or(Path::new("."));
+    let mut screenshots: Vec<LabeledScreenshot> = vec![];
+    for (index, line) in fs::read_to_string(labels)?.lines().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (path, names) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {} is not a `path = tags` pair", index + 1))?;
+        let names: Vec<&'static str> = names
+            .split(',')
+            .map(|name: &str| {
+                let name: &str = name.trim();
+                TAGS_STRINGS
+                    .iter()
+                    .copied()
+                    .find(|s: &&str| *s == name)
+                    .ok_or_else(|| format!("line {}: unknown tag `{}`", index + 1, name))
+            })
+            .collect::<Result<Vec<&'static str>, String>>()?;
+
+        let path: PathBuf = base.join(path.trim());
+        let mut image: Mat =
+            imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_COLOR_BGR)?;
+        if image.empty() {
+            return Err(format!("{}: could not read image", path.display()).into());
+        }
+        if config.photo_input {
+            image = perspective::rectify_photo(&image)?;
+        }
+        let panel: Mat = tag::crop_recruitment_roi(&image)?;
+        let gray: Mat = tag::preprocess_panel(&panel, config)?;
+        let boxes: Vec<Rect> = reading_order(tag::detect_tag_boxes(
+            &gray,
+            config.tag_box_size(config.server_language),
+        )?);
+        if boxes.len() != names.len() {
+            return Err(format!(
+                "{}: found {} tag boxes but {} labels",
+                path.display(),
+                boxes.len(),
+                names.len()
+            )
+            .into());
+        }
+        screenshots.push(LabeledScreenshot {
+            path,
+            gray,
+            tags: boxes.into_iter().zip(names).collect(),
+        });
+    }
+    Ok(screenshots)
+}
+
+/// Builds a tag library from labeled screenshots of the tag selection screen.
+///
+/// # Parameters
+/// - `labels`: A labels file as read by `read_labeled_set`.
+/// - `library`: The library directory. Crops are added to the existing content.
+/// - `config`: The active settings. Photos are rectified if `photo_input` is set, and the
+///   crops go through the same preprocessing as during a scan.
+///
+/// # Returns
+/// - `Result<usize, Box<dyn std::error::Error>>`: The number of reference crops written.
+///
+/// # Processing Steps
+/// 1. **Labeled Set**: The screenshots are read and their tag boxes paired with the labels
+///    by `read_labeled_set`.
+///
+/// 2. **Storage**: Each crop is normalized to `CLASSIFIER_SIZE` and written to
+///    `<library>/<Tag>/<screenshot>_<index>.png`.
+///
+/// # Example Usage
+/// ```rust
+/// let library: &Path = Path::new("templates/tags");
+/// let written: usize = build_library(Path::new("labels.txt"), library, &config)?;
+/// println!("{} reference crops written", written);
+/// ```
+///
+/// # Errors
+/// Returns an error if the labeled set cannot be read or a crop cannot be written.
+pub fn build_library(
+    labels: &Path,
+    library: &Path,
+    config: &Config,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut written: usize = 0;
+    for screenshot in read_labeled_set(labels, config)? {
+        let stem: String = screenshot
+            .path
+            .file_stem()
+            .map_or("screenshot".into(), |s: &std::ffi::OsStr| {
+                s.to_string_lossy().to_string()
+            });
+        for (i, (rect, name)) in screenshot.tags.iter().enumerate() {
+            let crop: Mat = normalize_crop(&tag::tag_crop(&screenshot.gray, rect, config)?)?;
+            let dir: PathBuf = library.join(name);
+            fs::create_dir_all(&dir)?;
+            let file: PathBuf = dir.join(format!("{}_{}.png", stem, i));
+            imgcodecs::imwrite_def(&file.to_string_lossy(), &crop)?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Resizes a crop to `CLASSIFIER_SIZE`.
+fn normalize_crop(crop: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+    let mut normalized: Mat = Mat::default();
+    imgproc::resize(
+        crop,
+        &mut normalized,
+        Size::new(CLASSIFIER_SIZE.0, CLASSIFIER_SIZE.1),
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )?;
+    Ok(normalized)
+}
+
+/// Sorts tag boxes into reading order: rows from top to bottom, boxes within a row from left
+/// to right. Boxes whose top edges differ by less than half a box height share a row.
+fn reading_order(mut boxes: Vec<Rect>) -> Vec<Rect> {
+    boxes.sort_by_key(|b: &Rect| b.y);
+    let mut rows: Vec<(usize, Rect)> = vec![];
+    let mut row: usize = 0;
+    let mut row_top: Option<i32> = None;
+    for b in boxes {
+        if row_top.is_some_and(|top: i32| b.y - top >= b.height / 2) {
+            row += 1;
+            row_top = None;
+        }
+        row_top.get_or_insert(b.y);
+        rows.push((row, b));
+    }
+    rows.sort_by_key(|(row, b): &(usize, Rect)| (*row, b.x));
+    rows.into_iter().map(|(_, b)| b).collect()
+}
+
+#[test]
+fn reading_order_sorts_rows_then_columns() {
+    let boxes: Vec<Rect> = vec![
+        Rect::new(200, 102, 150, 40),
+        Rect::new(380, 3, 150, 40),
+        Rect::new(20, 100, 150, 40),
+        Rect::new(200, 0, 150, 40),
+        Rect::new(20, 1, 150, 40),
+    ];
+    let xs: Vec<(i32, i32)> = reading_order(boxes)
+        .iter()
+        .map(|b: &Rect| (b.x, b.y))
+        .collect();
+    assert_eq!(xs, vec![(20, 1), (200, 0), (380, 3), (20, 100), (200, 102)]);
+}
@@ -0,0 +1,320 @@
+use super::{
+    cache::TagCache,
+    errors::ConfigError,
+    language::ServerLanguage,
+    matcher::{ConfusionTable, TagMatcher},
+    ocr::{EngineMode, OcrEngine, PageSegMode},
+    operators::OperatorDatabase,
+    preprocess::{Grayscale, Pipeline, Threshold, ThresholdMode},
+};
+use std::{fs, path::Path, sync::Arc};
+
+/// Runtime settings of retag.
+///
+/// The config file is a plain list of `key = value` lines. Empty lines and lines starting with
+/// `#` are ignored. Keys that are not present keep their default value.
+///
+/// # Fields
+/// - `ocr_backend`: The recognition engine, `tesseract`, `templates` or `onnx`
+///   (`ocr_backend`).
+/// - `server_language`: The game server the screenshots come from, `en`, `cn`, `jp` or `kr`.
+///   It selects the Tesseract trained data, the tag vocabulary, the tag whitelist and the
+///   matching strategy (`server_language`).
+/// - `detect_languages`: The server languages a tag selection screenshot is tried with. If
+///   any are given, the language of every screenshot is detected with
+///   `tag::detect_language` and `server_language` only applies to other screens. Given as a
+///   comma separated list such as `en, cn, jp, kr`; empty disables detection
+///   (`detect_languages`).
+/// - `display_language`: The language tags are shown in, `en`, `cn`, `jp` or `kr`
+///   (`display_language`).
+/// - `tessdata_dir`: Directory holding the Tesseract language data (`tessdata_dir`).
+/// - `tess_engine_mode`: The engine Tesseract runs internally, `legacy`, `lstm`, `combined`
+///   or `default` (`tess_engine_mode`).
+/// - `tess_user_words`: Whether Tesseract is given user words and patterns generated from the
+///   tag strings (`tess_user_words`).
+/// - `tess_variables`: Further Tesseract variables, set with one `tess.<name> = value` line
+///   each, e.g. `tess.load_system_dawg = 0`.
+/// - `tag_box_sizes`: Limits of the area of a tag box relative to the tag panel that replace
+///   the defaults of a language, set with one `tag_box_size.<language> = min, max` line each,
+///   e.g. `tag_box_size.jp = 0.005, 0.3`. Use `Config::tag_box_size` to read them.
+/// - `tag_page_seg_mode`: How tag crops are laid out for OCR, `auto`, `single_block`,
+///   `single_line`, `single_word` or `raw_line` (`tag_page_seg_mode`).
+/// - `tag_library`: Directory holding the reference crops of the template classifier
+///   (`tag_library`).
+/// - `tag_model`: The ONNX model file of the neural tag classifier (`tag_model`).
+/// - `threads`: Number of OCR workers. Tag crops and batches of images are recognized in
+///   parallel (`threads`).
+/// - `photo_input`: Whether inputs are camera photos of a screen rather than screenshots and
+///   need perspective correction first (`photo_input`).
+/// - `video_sample_rate`: Frames per second of footage inspected in videos
+///   (`video_sample_rate`).
+/// - `frame_diff_thresh`: Mean absolute difference in `[0, 1]` below which a video frame is
+///   treated as a duplicate of the previous one (`frame_diff_thresh`).
+/// - `min_screen_confidence`: Score a screenshot needs to be recognized as one of the
+///   recruitment screens (`min_screen_confidence`).
+/// - `preprocess_image`: Pipeline applied to the tag panel before tag boxes are detected
+///   (`preprocess_image`). It must produce a grayscale image.
+/// - `preprocess_tag`: Pipeline applied to every tag crop before OCR (`preprocess_tag`).
+/// - `matcher`: How OCR readings are compared with the tag strings, `difflib` or `confusion`
+///   (`matcher`). Setting `confusion_table` selects the confusion matcher with the
+///   confusions of that file instead of the built-in ones.
+/// - `weak_match_thresh`: Match ratio below which a tag crop is read again with the retry
+///   passes and the tag is chosen by voting. `0` disables the retries (`weak_match_thresh`).
+/// - `operators`: The operators recruitment results are matched against. The built-in
+///   recruitment pool can be replaced by a database file (`operator_database`).
+/// - `tag_cache`: The cache of recognized tag crops, or `None` if caching is disabled. It is
+///   off by default. `tag_cache = true` keeps it in memory for one run; `tag_cache_file`
+///   persists it in that file between runs, unless an earlier line set `tag_cache = false`.
+/// - `debug_dir`: If set, every preprocessing stage writes its output to this directory
+///   (`debug_dir`).
+///
+/// # Example
+/// ```text
+/// # retag.conf
+/// tessdata_dir = /usr/share/tessdata
+/// tag_page_seg_mode = single_word
+/// tess.load_system_dawg = 0
+/// photo_input = true
+/// preprocess_tag = clahe(2.0, 8), threshold(otsu), open(2)
+/// debug_dir = debug/
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub ocr_backend: OcrEngine,
+    pub server_language: ServerLanguage,
+    pub detect_languages: Vec<ServerLanguage>,
+    pub display_language: ServerLanguage,
+    pub tessdata_dir: String,
+    pub tess_engine_mode: EngineMode,
+    pub tess_user_words: bool,
+    pub tess_variables: Vec<(String, String)>,
+    pub tag_box_sizes: Vec<(ServerLanguage, (f64, f64))>,
+    pub tag_page_seg_mode: PageSegMode,
+    pub tag_library: String,
+    pub tag_model: String,
+    pub threads: usize,
+    pub photo_input: bool,
+    pub video_sample_rate: f64,
+    pub frame_diff_thresh: f64,
+    pub min_screen_confidence: f64,
+    pub preprocess_image: Pipeline,
+    pub preprocess_tag: Pipeline,
+    pub matcher: TagMatcher,
+    pub weak_match_thresh: f64,
+    pub operators: OperatorDatabase,
+    pub tag_cache: Option<Arc<TagCache>>,
+    pub debug_dir: Option<String>,
+    /// Set by `tag_cache = false`, so a later `tag_cache_file` does not enable the cache.
+    pub(crate) tag_cache_disabled: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            ocr_backend: OcrEngine::default(),
+            server_language: ServerLanguage::default(),
+            detect_languages: vec![],
+            display_language: ServerLanguage::default(),
+            tessdata_dir: "/usr/share/tessdata".into(),
+            tess_engine_mode: EngineMode::default(),
+            tess_user_words: true,
+            tess_variables: vec![],
+            tag_box_sizes: vec![],
+            tag_page_seg_mode: PageSegMode::SingleLine,
+            tag_library: "templates/tags".into(),
+            tag_model: "models/tags.onnx".into(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            photo_input: false,
+            video_sample_rate: 2.0,
+            frame_diff_thresh: 0.02,
+            min_screen_confidence: 0.6,
+            preprocess_image: Pipeline::new("preprocess_image", vec![Arc::new(Grayscale)]),
+            preprocess_tag: Pipeline::new(
+                "preprocess_tag",
+                vec![Arc::new(Threshold(ThresholdMode::Fixed(160.0)))],
+            ),
+            matcher: TagMatcher::default(),
+            weak_match_thresh: 0.75,
+            operators: OperatorDatabase::default(),
+            tag_cache: None,
+            debug_dir: None,
+            tag_cache_disabled: false,
+        }
+    }
+}
+
+impl Config {
+    /// Reads a config file, starting from the default values.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, a line is malformed, a key is unknown or
+    /// a value cannot be parsed.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config: Config = Config::default();
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(ConfigError::InvalidLine(index + 1))?;
+            config.set(key.trim(), value.trim())?;
+        }
+        Ok(config)
+    }
+
+    /// Sets a single setting from its textual representation.
+    ///
+    /// This is used by `Config::load` and for command line overrides.
+    ///
+    /// # Errors
+    /// Returns an error if the key is unknown or the value cannot be parsed.
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "ocr_backend" => self.ocr_backend = parse_value(key, value)?,
+            "server_language" => self.server_language = parse_value(key, value)?,
+            "detect_languages" => {
+                self.detect_languages = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|language: &&str| !language.is_empty())
+                    .map(|language: &str| parse_value(key, language))
+                    .collect::<Result<Vec<ServerLanguage>, ConfigError>>()?
+            }
+            "display_language" => self.display_language = parse_value(key, value)?,
+            "tessdata_dir" => self.tessdata_dir = value.into(),
+            "tess_engine_mode" => self.tess_engine_mode = parse_value(key, value)?,
+            "tess_user_words" => self.tess_user_words = parse_value(key, value)?,
+            "tag_page_seg_mode" => self.tag_page_seg_mode = parse_value(key, value)?,
+            "tag_library" => self.tag_library = value.into(),
+            "tag_model" => self.tag_model = value.into(),
+            "threads" => self.threads = parse_value(key, value)?,
+            "photo_input" => self.photo_input = parse_value(key, value)?,
+            "video_sample_rate" => self.video_sample_rate = parse_value(key, value)?,
+            "frame_diff_thresh" => self.frame_diff_thresh = parse_value(key, value)?,
+            "min_screen_confidence" => self.min_screen_confidence = parse_value(key, value)?,
+            "preprocess_image" => self.preprocess_image = Pipeline::parse(key, value)?,
+            "preprocess_tag" => self.preprocess_tag = Pipeline::parse(key, value)?,
+            "matcher" => {
+                self.matcher = match (value, &self.matcher) {
+                    ("difflib", _) => TagMatcher::Difflib,
+                    ("confusion", TagMatcher::Confusion(table)) => {
+                        TagMatcher::Confusion(table.clone())
+                    }
+                    ("confusion", _) => TagMatcher::Confusion(ConfusionTable::default()),
+                    _ => return Err(ConfigError::InvalidValue(key.into(), value.into())),
+                }
+            }
+            "confusion_table" => {
+                self.matcher = TagMatcher::Confusion(
+                    ConfusionTable::load(Path::new(value))
+                        .map_err(|_| ConfigError::InvalidValue(key.into(), value.into()))?,
+                )
+            }
+            "weak_match_thresh" => self.weak_match_thresh = parse_value(key, value)?,
+            "operator_database" => {
+                self.operators = OperatorDatabase::load(Path::new(value))
+                    .map_err(|_| ConfigError::InvalidValue(key.into(), value.into()))?
+            }
+            "tag_cache" => {
+                let enabled: bool = parse_value(key, value)?;
+                self.tag_cache_disabled = !enabled;
+                self.tag_cache = match (enabled, self.tag_cache.take()) {
+                    (false, _) => None,
+                    (true, cache) => cache.or_else(|| Some(Arc::new(TagCache::default()))),
+                }
+            }
+            "tag_cache_file" if self.tag_cache_disabled => {}
+            "tag_cache_file" => {
+                self.tag_cache = Some(Arc::new(
+                    TagCache::load(Path::new(value))
+                        .map_err(|_| ConfigError::InvalidValue(key.into(), value.into()))?,
+                ))
+            }
+            "debug_dir" => self.debug_dir = Some(value.into()).filter(|v: &String| !v.is_empty()),
+            _ if key.starts_with("tag_box_size.") => {
+                let language: ServerLanguage = key["tag_box_size.".len()..]
+                    .parse()
+                    .map_err(|_| ConfigError::UnknownKey(key.into()))?;
+                let limits: (f64, f64) = match value.split_once(',') {
+                    Some((min, max)) => {
+                        (parse_value(key, min.trim())?, parse_value(key, max.trim())?)
+                    }
+                    None => return Err(ConfigError::InvalidValue(key.into(), value.into())),
+                };
+                if !(0.0 <= limits.0 && limits.0 < limits.1) {
+                    return Err(ConfigError::InvalidValue(key.into(), value.into()));
+                }
+                self.tag_box_sizes.retain(|(l, _)| *l != language);
+                self.tag_box_sizes.push((language, limits));
+            }
+            _ => {
+                let Some(name) = key.strip_prefix("tess.").filter(|n: &&str| !n.is_empty()) else {
+                    return Err(ConfigError::UnknownKey(key.into()));
+                };
+                self.tess_variables.retain(|(n, _)| n != name);
+                self.tess_variables.push((name.into(), value.into()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the limits of the area of a tag box relative to the tag panel for a language,
+    /// as `(min, max)`: the configured `tag_box_size.<language>` or the language default.
+    pub fn tag_box_size(&self, language: ServerLanguage) -> (f64, f64) {
+        self.tag_box_sizes
+            .iter()
+            .find(|(l, _)| *l == language)
+            .map_or(language.tag_box_size(), |(_, limits)| *limits)
+    }
+
+    /// Returns the widest limits of the area of a tag box among `languages`, so that the tag
+    /// boxes of any of them are found. Falls back to the limits of `server_language` if
+    /// `languages` is empty.
+    pub fn widest_tag_box_size(&self, languages: &[ServerLanguage]) -> (f64, f64) {
+        languages
+            .iter()
+            .map(|language: &ServerLanguage| self.tag_box_size(*language))
+            .reduce(|(min_a, max_a): (f64, f64), (min_b, max_b): (f64, f64)| {
+                (min_a.min(min_b), max_a.max(max_b))
+            })
+            .unwrap_or(self.tag_box_size(self.server_language))
+    }
+
+    /// Returns the directory debug images are written to, if debug output is enabled.
+    pub fn debug_dir(&self) -> Option<&Path> {
+        self.debug_dir.as_deref().map(Path::new)
+    }
+}
+
+/// Parses a config value, reporting the offending key on failure.
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse::<T>()
+        .map_err(|_| ConfigError::InvalidValue(key.into(), value.into()))
+}
+
+#[test]
+fn tag_cache_is_off_by_default_and_stays_off_once_disabled() -> Result<(), ConfigError> {
+    let path: String = std::env::temp_dir()
+        .join(format!("retag-config-cache-{}.txt", std::process::id()))
+        .to_string_lossy()
+        .into();
+    let mut config: Config = Config::default();
+    assert!(config.tag_cache.is_none());
+    config.set("tag_cache", "true")?;
+    assert!(config.tag_cache.is_some());
+
+    let mut config: Config = Config::default();
+    config.set("tag_cache", "false")?;
+    config.set("tag_cache_file", &path)?;
+    assert!(config.tag_cache.is_none());
+
+    let mut config: Config = Config::default();
+    config.set("tag_cache_file", &path)?;
+    assert!(config.tag_cache.is_some());
+    config.set("tag_cache", "false")?;
+    assert!(config.tag_cache.is_none());
+    Ok(())
+}
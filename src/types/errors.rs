@@ -1,4 +1,25 @@
+use std::fmt;
+
 #[derive(Debug)]
 pub(crate) enum TagError {
 	InvalidTagString,
-}
\ No newline at end of file
+}
+
+#[derive(Debug)]
+pub(crate) enum ConfigError {
+	InvalidLine(usize),
+	UnknownKey(String),
+	InvalidValue(String, String),
+}
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidLine(line) => write!(f, "line {} is not a `key = value` pair", line),
+			Self::UnknownKey(key) => write!(f, "unknown config key `{}`", key),
+			Self::InvalidValue(key, value) => write!(f, "invalid value `{}` for `{}`", value, key),
+		}
+	}
+}
+
+impl std::error::Error for ConfigError {}
\ No newline at end of file
@@ -0,0 +1,344 @@
+use super::{
+    matcher::TagMatcher,
+    ocr,
+    tag::{TagType, TAGS_STRINGS},
+};
+use std::fmt;
+
+/// The tag strings of the simplified-Chinese client, in the order of `TAGS_STRINGS`.
+static CHINESE_TAG_STRINGS: [&str; 28] = [
+    "医疗干员",
+    "术师干员",
+    "先锋干员",
+    "近卫干员",
+    "重装干员",
+    "辅助干员",
+    "近战位",
+    "削弱",
+    "快速复活",
+    "位移",
+    "召唤",
+    "支援",
+    "生存",
+    "元素",
+    "远程位",
+    "费用回复",
+    "新手",
+    "减速",
+    "群攻",
+    "狙击干员",
+    "控场",
+    "治疗",
+    "输出",
+    "爆发",
+    "资深干员",
+    "特种干员",
+    "支援机械",
+    "高级资深干员",
+];
+
+/// The tag strings of the Japanese client, in the order of `TAGS_STRINGS`.
+static JAPANESE_TAG_STRINGS: [&str; 28] = [
+    "医療タイプ",
+    "術師タイプ",
+    "先鋒タイプ",
+    "前衛タイプ",
+    "重装タイプ",
+    "補助タイプ",
+    "近距離",
+    "弱化",
+    "高速再配置",
+    "強制移動",
+    "召喚",
+    "支援",
+    "生存",
+    "元素",
+    "遠距離",
+    "COST回復",
+    "初期",
+    "減速",
+    "範囲攻撃",
+    "狙撃タイプ",
+    "牽制",
+    "治療",
+    "火力",
+    "爆発力",
+    "エリート",
+    "特殊タイプ",
+    "ロボット",
+    "上級エリート",
+];
+
+/// The tag strings of the Korean client, in the order of `TAGS_STRINGS`.
+static KOREAN_TAG_STRINGS: [&str; 28] = [
+    "메딕 타입",
+    "캐스터 타입",
+    "뱅가드 타입",
+    "가드 타입",
+    "디펜더 타입",
+    "서포터 타입",
+    "근거리",
+    "디버프",
+    "쾌속부활",
+    "강제이동",
+    "소환",
+    "지원",
+    "생존",
+    "원소",
+    "원거리",
+    "코스트+",
+    "신입",
+    "감속",
+    "범위공격",
+    "스나이퍼 타입",
+    "군중제어",
+    "힐링",
+    "딜러",
+    "폭발력",
+    "특별채용",
+    "스페셜리스트 타입",
+    "로봇",
+    "고급특별채용",
+];
+
+/// Limits of the area of a tag box relative to the tag panel on the global and CN clients, as
+/// `(min, max)`.
+static TAG_BOX_SIZE: (f64, f64) = (0.005, 0.250);
+/// Limits of the area of a tag box on the JP client, whose buttons are wider to fit the
+/// katakana suffixes.
+static JAPANESE_TAG_BOX_SIZE: (f64, f64) = (0.005, 0.300);
+
+/// Character-level difflib matching, used for scripts the confusion table does not cover.
+static CHARACTER_MATCHER: TagMatcher = TagMatcher::Difflib;
+/// Jamo-level difflib matching, used for Hangul.
+static JAMO_MATCHER: TagMatcher = TagMatcher::Jamo;
+
+/// The game server a screenshot comes from, which decides the language of the tag buttons.
+///
+/// Every language has its own tag vocabulary, aligned with `TAGS_STRINGS`, so readings are
+/// matched against the strings the client actually shows and then mapped to the same
+/// `TagType` variants.
+///
+/// # Variants
+/// - `English`: The global server.
+/// - `Chinese`: The CN server, with simplified-Chinese tags read with the `chi_sim` data.
+/// - `Japanese`: The JP server, with katakana and kanji tags read with the `jpn` data.
+/// - `Korean`: The KR server, with Hangul tags read with the `kor` data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ServerLanguage {
+    #[default]
+    English,
+    Chinese,
+    Japanese,
+    Korean,
+}
+
+impl std::str::FromStr for ServerLanguage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(ServerLanguage::English),
+            "cn" => Ok(ServerLanguage::Chinese),
+            "jp" => Ok(ServerLanguage::Japanese),
+            "kr" => Ok(ServerLanguage::Korean),
+            _ => Err(format!("unknown server language `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for ServerLanguage {
+    /// Writes the code of the language as accepted by `FromStr`, e.g. `cn`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code: &str = match self {
+            ServerLanguage::English => "en",
+            ServerLanguage::Chinese => "cn",
+            ServerLanguage::Japanese => "jp",
+            ServerLanguage::Korean => "kr",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+impl ServerLanguage {
+    /// Every supported language, in the order tag strings are tried when parsing.
+    pub const ALL: [ServerLanguage; 4] = [
+        ServerLanguage::English,
+        ServerLanguage::Chinese,
+        ServerLanguage::Japanese,
+        ServerLanguage::Korean,
+    ];
+
+    /// Returns the name of the Tesseract trained data for the language.
+    pub fn tessdata_language(self) -> &'static str {
+        match self {
+            ServerLanguage::English => "eng",
+            ServerLanguage::Chinese => "chi_sim",
+            ServerLanguage::Japanese => "jpn",
+            ServerLanguage::Korean => "kor",
+        }
+    }
+
+    /// Returns the tag strings shown by the client, in the order of `TAGS_STRINGS`.
+    pub fn tag_strings(self) -> &'static [&'static str; 28] {
+        match self {
+            ServerLanguage::English => &TAGS_STRINGS,
+            ServerLanguage::Chinese => &CHINESE_TAG_STRINGS,
+            ServerLanguage::Japanese => &JAPANESE_TAG_STRINGS,
+            ServerLanguage::Korean => &KOREAN_TAG_STRINGS,
+        }
+    }
+
+    /// Returns the characters the OCR backend may emit when reading tags.
+    ///
+    /// For English this is `ocr::TAG_WHITELIST`. For the other languages it is every
+    /// character of the vocabulary.
+    pub fn tag_whitelist(self) -> String {
+        if self == ServerLanguage::English {
+            return ocr::TAG_WHITELIST.into();
+        }
+        let mut chars: Vec<char> = self.tag_strings().iter().flat_map(|s| s.chars()).collect();
+        chars.sort();
+        chars.dedup();
+        chars.into_iter().collect()
+    }
+
+    /// Returns the union of the tag whitelists of `languages`, for reading tags whose
+    /// language is not known yet.
+    pub fn combined_tag_whitelist(languages: &[ServerLanguage]) -> String {
+        let mut chars: Vec<char> = languages
+            .iter()
+            .flat_map(|language: &ServerLanguage| {
+                language.tag_whitelist().chars().collect::<Vec<char>>()
+            })
+            .collect();
+        chars.sort();
+        chars.dedup();
+        chars.into_iter().collect()
+    }
+
+    /// Returns the minimum number of characters of a reading worth matching. Chinese, Japanese
+    /// and Korean tags can be two characters long.
+    pub fn min_tag_length(self) -> usize {
+        match self {
+            ServerLanguage::English => 3,
+            ServerLanguage::Chinese | ServerLanguage::Japanese | ServerLanguage::Korean => 2,
+        }
+    }
+
+    /// Returns the default limits of the area of a tag box relative to the tag panel, as
+    /// `(min, max)`. They can be overridden per language with `Config::tag_box_size`.
+    pub fn tag_box_size(self) -> (f64, f64) {
+        match self {
+            ServerLanguage::English | ServerLanguage::Chinese | ServerLanguage::Korean => {
+                TAG_BOX_SIZE
+            }
+            ServerLanguage::Japanese => JAPANESE_TAG_BOX_SIZE,
+        }
+    }
+
+    /// Cleans a reading up for matching. Surrounding whitespace is trimmed, and for Chinese
+    /// and Japanese the spaces Tesseract puts between characters are removed. Japanese
+    /// readings also have full-width Latin letters and digits folded to ASCII, because `COST`
+    /// is printed in either width. Korean tags are separated into words, so runs of
+    /// whitespace are collapsed to a single space instead.
+    pub fn normalize(self, text: &str) -> String {
+        match self {
+            ServerLanguage::Korean => text.split_whitespace().collect::<Vec<&str>>().join(" "),
+            ServerLanguage::English => text.trim().to_string(),
+            ServerLanguage::Chinese => text.chars().filter(|c: &char| !c.is_whitespace()).collect(),
+            ServerLanguage::Japanese => text
+                .chars()
+                .filter(|c: &char| !c.is_whitespace())
+                .map(fold_full_width)
+                .collect(),
+        }
+    }
+
+    /// Returns the matcher readings are compared with.
+    ///
+    /// The configured matcher is used for English. Chinese and Japanese are matched character
+    /// by character with difflib, because the confusion table only describes Latin glyphs.
+    /// Korean is matched jamo by jamo, so a syllable misread in one component is not lost
+    /// entirely.
+    pub fn matcher(self, configured: &TagMatcher) -> &TagMatcher {
+        match self {
+            ServerLanguage::English => configured,
+            ServerLanguage::Chinese | ServerLanguage::Japanese => &CHARACTER_MATCHER,
+            ServerLanguage::Korean => &JAMO_MATCHER,
+        }
+    }
+
+    /// Returns the word the client appends to the class tags, e.g. `干员` in `医疗干员`. Users
+    /// commonly leave it out when typing a tag.
+    fn class_suffix(self) -> Option<&'static str> {
+        match self {
+            ServerLanguage::English => None,
+            ServerLanguage::Chinese => Some("干员"),
+            ServerLanguage::Japanese => Some("タイプ"),
+            ServerLanguage::Korean => Some("타입"),
+        }
+    }
+
+    /// Parses a tag string of the language into a `TagType`.
+    ///
+    /// The text is normalized like a reading and compared without whitespace, so `메딕타입`
+    /// is accepted for `메딕 타입`. Class tags are also accepted without their class suffix,
+    /// e.g. `医療` for `医療タイプ`.
+    pub fn parse_tag(self, text: &str) -> Option<TagType> {
+        let compact =
+            |s: &str| -> String { s.chars().filter(|c: &char| !c.is_whitespace()).collect() };
+        let text: String = compact(&self.normalize(text));
+        let index: usize = self.tag_strings().iter().position(|tag_string: &&str| {
+            let tag_string: String = compact(tag_string);
+            tag_string == text
+                || self
+                    .class_suffix()
+                    .and_then(|suffix: &str| tag_string.strip_suffix(suffix))
+                    .is_some_and(|stem: &str| !stem.is_empty() && stem == text)
+        })?;
+        TAGS_STRINGS[index].parse::<TagType>().ok()
+    }
+
+    /// Returns the localized string of a tag given by its entry in `TAGS_STRINGS`.
+    pub fn localize(self, tag_string: &str) -> Option<&'static str> {
+        TAGS_STRINGS
+            .iter()
+            .position(|s: &&str| *s == tag_string)
+            .map(|index: usize| self.tag_strings()[index])
+    }
+}
+
+/// Maps a full-width ASCII variant, e.g. `Ｃ`, to its ASCII character and leaves every other
+/// character unchanged.
+fn fold_full_width(c: char) -> char {
+    match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+#[test]
+fn japanese_readings_are_normalized_and_localized() {
+    let language: ServerLanguage = ServerLanguage::Japanese;
+    assert_eq!(language.normalize(" ＣＯＳＴ 回 復\n"), "COST回復");
+    assert_eq!(language.localize("Dp-Recovery"), Some("COST回復"));
+    assert_eq!(language.localize("Top-Operator"), Some("上級エリート"));
+    assert!(language.tag_whitelist().contains('ト'));
+}
+
+#[test]
+fn tags_parse_from_any_language() {
+    let parse = |s: &str| s.parse::<TagType>().ok();
+    assert_eq!(parse("Top Operator"), Some(TagType::TopOperator));
+    assert_eq!(parse("医疗干员"), Some(TagType::Medic));
+    assert_eq!(parse("医療"), Some(TagType::Medic));
+    assert_eq!(parse("스나이퍼타입"), Some(TagType::Sniper));
+    assert_eq!(parse("ＣＯＳＴ回復"), Some(TagType::DpRecovery));
+    assert_eq!(parse("支援机械"), Some(TagType::Robot));
+    assert_eq!(parse("机械"), None);
+    assert_eq!(
+        TagType::Sniper.display_name(ServerLanguage::Korean),
+        "스나이퍼 타입"
+    );
+}
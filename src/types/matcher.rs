@@ -0,0 +1,378 @@
+use super::{
+    classifier::{self, LabeledScreenshot},
+    config::Config,
+    language::ServerLanguage,
+    ocr::{OcrBackend, OcrCandidate},
+    tag,
+};
+use difflib::sequencematcher::SequenceMatcher;
+use opencv::core::{Mat, Rect};
+use std::{fmt, fs, path::Path};
+
+/// Cost of inserting or deleting a character that is not covered by a confusion.
+static INDEL_COST: f64 = 1.0;
+/// Cost of substituting a character by another that is not covered by a confusion.
+static SUBSTITUTION_COST: f64 = 1.0;
+/// Cost of reading a character in the wrong case, e.g. `o` for `O`.
+static CASE_COST: f64 = 0.25;
+/// The first precomposed Hangul syllable, `가`.
+static HANGUL_SYLLABLE_BASE: u32 = 0xAC00;
+/// Number of precomposed Hangul syllables: 19 leading consonants, 21 vowels and 28 trailing
+/// consonant slots.
+static HANGUL_SYLLABLE_COUNT: u32 = 19 * 21 * 28;
+
+/// Misreadings Tesseract commonly makes on the tag font, as `(expected, read, cost)`. An empty
+/// side stands for a dropped or inserted string. The costs are hand-picked, not measured; how
+/// the table compares with difflib on real screenshots is what `evaluate` reports for a
+/// labeled set.
+static DEFAULT_CONFUSIONS: [(&str, &str, f64); 12] = [
+    ("m", "rn", 0.2),
+    ("d", "cl", 0.3),
+    ("w", "vv", 0.3),
+    ("I", "l", 0.1),
+    ("l", "1", 0.2),
+    ("I", "1", 0.2),
+    ("O", "0", 0.1),
+    ("o", "0", 0.2),
+    ("S", "5", 0.3),
+    ("B", "8", 0.3),
+    ("-", "", 0.1),
+    ("-", " ", 0.1),
+];
+
+/// The costs of the misreadings a weighted edit distance forgives.
+///
+/// Confusions apply in both directions and may span several characters, so `rn` read for `m`
+/// costs as much as `m` read for `rn`. Strings not covered by a confusion cost `INDEL_COST`
+/// per inserted or deleted character and `SUBSTITUTION_COST` per substituted one.
+///
+/// The table is written by hand. Learning the confusions and their costs from a labeled set is
+/// not supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfusionTable {
+    confusions: Vec<(Vec<char>, Vec<char>, f64)>,
+}
+
+impl Default for ConfusionTable {
+    /// Returns the table of `DEFAULT_CONFUSIONS`.
+    fn default() -> Self {
+        ConfusionTable {
+            confusions: DEFAULT_CONFUSIONS
+                .iter()
+                .map(|(expected, read, cost): &(&str, &str, f64)| {
+                    (expected.chars().collect(), read.chars().collect(), *cost)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ConfusionTable {
+    /// Reads a confusion table from a file with one `expected read cost` line per confusion.
+    /// An empty string is written as `""`. Empty lines and lines starting with `#` are
+    /// ignored.
+    ///
+    /// # Example
+    /// ```text
+    /// # tag-font.confusions
+    /// m rn 0.2
+    /// - "" 0.1
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, a line is malformed or both sides of a
+    /// confusion are empty.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut confusions: Vec<(Vec<char>, Vec<char>, f64)> = vec![];
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [expected, read, cost] = fields.as_slice() else {
+                return Err(
+                    format!("line {} is not an `expected read cost` entry", index + 1).into(),
+                );
+            };
+            let side = |s: &str| -> Vec<char> {
+                match s {
+                    "\"\"" => vec![],
+                    _ => s.chars().collect(),
+                }
+            };
+            let (expected, read): (Vec<char>, Vec<char>) = (side(*expected), side(*read));
+            if expected.is_empty() && read.is_empty() {
+                return Err(format!("line {}: both sides are empty", index + 1).into());
+            }
+            confusions.push((expected, read, cost.parse::<f64>()?));
+        }
+        Ok(ConfusionTable { confusions })
+    }
+
+    /// Returns the weighted edit distance between the expected and the read string.
+    pub fn distance(&self, expected: &str, read: &str) -> f64 {
+        let a: Vec<char> = expected.chars().collect();
+        let b: Vec<char> = read.chars().collect();
+        let mut d: Vec<Vec<f64>> = vec![vec![f64::INFINITY; b.len() + 1]; a.len() + 1];
+        d[0][0] = 0.0;
+        for i in 0..=a.len() {
+            for j in 0..=b.len() {
+                let mut best: f64 = d[i][j];
+                if i > 0 {
+                    best = best.min(d[i - 1][j] + INDEL_COST);
+                }
+                if j > 0 {
+                    best = best.min(d[i][j - 1] + INDEL_COST);
+                }
+                if i > 0 && j > 0 {
+                    best = best.min(d[i - 1][j - 1] + substitution_cost(a[i - 1], b[j - 1]));
+                }
+                for (x, y, cost) in &self.confusions {
+                    for (from, to) in [(x, y), (y, x)] {
+                        if from.len() <= i
+                            && to.len() <= j
+                            && a[i - from.len()..i] == from[..]
+                            && b[j - to.len()..j] == to[..]
+                        {
+                            best = best.min(d[i - from.len()][j - to.len()] + cost);
+                        }
+                    }
+                }
+                d[i][j] = best;
+            }
+        }
+        d[a.len()][b.len()]
+    }
+
+    /// Returns the similarity of the two strings in `[0, 1]`: one minus their weighted edit
+    /// distance relative to the length of the longer string.
+    pub fn similarity(&self, expected: &str, read: &str) -> f64 {
+        let length: usize = expected.chars().count().max(read.chars().count());
+        if length == 0 {
+            return 1.0;
+        }
+        1.0 - (self.distance(expected, read) / length as f64).min(1.0)
+    }
+}
+
+/// Returns the cost of reading `read` where `expected` is printed.
+fn substitution_cost(expected: char, read: char) -> f64 {
+    if expected == read {
+        0.0
+    } else if expected.to_lowercase().eq(read.to_lowercase()) {
+        CASE_COST
+    } else {
+        SUBSTITUTION_COST
+    }
+}
+
+/// How OCR readings are compared with the tag strings.
+///
+/// # Variants
+/// - `Difflib`: The ratio of difflib's `SequenceMatcher`, computed over characters so that
+///   multi-byte scripts are compared character by character.
+/// - `Confusion`: A weighted edit distance that forgives the misreadings listed in its
+///   `ConfusionTable`.
+/// - `Jamo`: The difflib ratio over Hangul syllables decomposed into their jamo with
+///   `decompose_hangul`, so a misread syllable that keeps its initial consonant or vowel
+///   still counts partially.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum TagMatcher {
+    #[default]
+    Difflib,
+    Confusion(ConfusionTable),
+    Jamo,
+}
+
+impl TagMatcher {
+    /// Returns the name of the matcher as used by the `matcher` setting.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TagMatcher::Difflib => "difflib",
+            TagMatcher::Confusion(_) => "confusion",
+            TagMatcher::Jamo => "jamo",
+        }
+    }
+
+    /// Returns the similarity between a tag string and an OCR reading, in `[0, 1]`.
+    pub fn similarity(&self, tag_string: &str, text: &str) -> f64 {
+        match self {
+            TagMatcher::Difflib => {
+                let a: Vec<char> = tag_string.chars().collect();
+                let b: Vec<char> = text.chars().collect();
+                SequenceMatcher::new(&a, &b).ratio() as f64
+            }
+            TagMatcher::Confusion(table) => table.similarity(tag_string, text),
+            TagMatcher::Jamo => {
+                let a: Vec<char> = decompose_hangul(tag_string);
+                let b: Vec<char> = decompose_hangul(text);
+                SequenceMatcher::new(&a, &b).ratio() as f64
+            }
+        }
+    }
+}
+
+/// Splits every precomposed Hangul syllable into its leading consonant, vowel and optional
+/// trailing consonant, as conjoining jamo. Whitespace is dropped, because Tesseract does not
+/// reliably keep the space of two-word tags. Other characters are kept as they are.
+///
+/// # Example Usage
+/// ```rust
+/// let jamo: Vec<char> = decompose_hangul("각 가");
+/// assert_eq!(jamo, vec!['\u{1100}', '\u{1161}', '\u{11A8}', '\u{1100}', '\u{1161}']);
+/// ```
+pub(crate) fn decompose_hangul(text: &str) -> Vec<char> {
+    let mut jamo: Vec<char> = vec![];
+    for c in text.chars().filter(|c: &char| !c.is_whitespace()) {
+        let Some(index) = (c as u32)
+            .checked_sub(HANGUL_SYLLABLE_BASE)
+            .filter(|index: &u32| *index < HANGUL_SYLLABLE_COUNT)
+        else {
+            jamo.push(c);
+            continue;
+        };
+        let parts: [u32; 3] = [
+            0x1100 + index / (21 * 28),
+            0x1161 + index % (21 * 28) / 28,
+            0x11A7 + index % 28,
+        ];
+        // A trailing index of 0 means the syllable has no final consonant.
+        let count: usize = if index % 28 == 0 { 2 } else { 3 };
+        jamo.extend(
+            parts[..count]
+                .iter()
+                .filter_map(|part: &u32| char::from_u32(*part)),
+        );
+    }
+    jamo
+}
+
+/// The accuracy of a `TagMatcher` on a labeled set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatcherScore {
+    pub matcher: &'static str,
+    pub correct: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for MatcherScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let accuracy: f64 = if self.total == 0 {
+            0.0
+        } else {
+            self.correct as f64 / self.total as f64 * 100.0
+        };
+        write!(
+            f,
+            "{}: {}/{} tags correct ({:.1}%)",
+            self.matcher, self.correct, self.total, accuracy
+        )
+    }
+}
+
+/// Measures how many tags of a labeled set each matcher gets right.
+///
+/// # Parameters
+/// - `labels`: A labels file as read by `classifier::read_labeled_set`.
+/// - `backend`: The OCR backend the tag crops are read with. Its whitelist must be set.
+/// - `config`: The active settings. The configured confusion table is evaluated if the
+///   `confusion` matcher is selected, otherwise the default table.
+///
+/// # Returns
+/// - `Result<Vec<MatcherScore>, Box<dyn std::error::Error>>`: The score of difflib and of the
+///   confusion matcher, in that order.
+///
+/// # Processing Steps
+/// 1. **Reading**: Every labeled tag box is cut out with `tag::tag_crop` and read once.
+///
+/// 2. **Matching**: The readings are matched with each matcher on their own, without retries
+///    or the joint assignment, so the scores only differ by the matcher.
+///
+/// Only English tags can be evaluated. The other server languages always use their own
+/// matcher (see `ServerLanguage::matcher`), so both scores would be identical.
+///
+/// # Example Usage
+/// ```rust
+/// for score in evaluate(Path::new("labels.txt"), &mut backend, &config)? {
+///     println!("{}", score);
+/// }
+/// ```
+///
+/// # Errors
+/// Returns an error if `server_language` is not English, the labeled set cannot be read or
+/// recognition fails.
+pub fn evaluate(
+    labels: &Path,
+    backend: &mut dyn OcrBackend,
+    config: &Config,
+) -> Result<Vec<MatcherScore>, Box<dyn std::error::Error>> {
+    if config.server_language != ServerLanguage::English {
+        return Err(format!(
+            "matchers can only be compared on English tags, {} tags are always matched with {}",
+            config.server_language,
+            config.server_language.matcher(&config.matcher).name()
+        )
+        .into());
+    }
+    let table: ConfusionTable = match &config.matcher {
+        TagMatcher::Confusion(table) => table.clone(),
+        TagMatcher::Difflib | TagMatcher::Jamo => ConfusionTable::default(),
+    };
+    let matchers: [TagMatcher; 2] = [TagMatcher::Difflib, TagMatcher::Confusion(table)];
+    let mut scores: Vec<MatcherScore> = matchers
+        .iter()
+        .map(|matcher: &TagMatcher| MatcherScore {
+            matcher: matcher.name(),
+            correct: 0,
+            total: 0,
+        })
+        .collect();
+
+    for screenshot in classifier::read_labeled_set(labels, config)? {
+        let LabeledScreenshot { gray, tags, .. } = screenshot;
+        let crops: Vec<Mat> = tags
+            .iter()
+            .map(|(rect, _): &(Rect, &'static str)| tag::tag_crop(&gray, rect, config))
+            .collect::<Result<Vec<Mat>, Box<dyn std::error::Error>>>()?;
+        let readings: Vec<Vec<OcrCandidate>> = backend.recognize_batch(&crops)?;
+        for ((_, label), candidates) in tags.iter().zip(&readings) {
+            for (matcher, score) in matchers.iter().zip(scores.iter_mut()) {
+                let matched: Option<&'static str> =
+                    tag::match_candidates(candidates, matcher, config.server_language)
+                        .map(|(tag_string, _)| tag_string);
+                score.total += 1;
+                if matched == Some(*label) {
+                    score.correct += 1;
+                }
+            }
+        }
+    }
+    Ok(scores)
+}
+
+#[test]
+fn confusion_matcher_forgives_ocr_misreadings() {
+    let table: ConfusionTable = ConfusionTable::default();
+    assert_eq!(table.distance("Summon", "Summon"), 0.0);
+    assert!((table.distance("Summon", "Surnrnon") - 0.4).abs() < 1e-9);
+    assert!((table.distance("Dp-Recovery", "Dp Recovery") - 0.1).abs() < 1e-9);
+    assert!((table.distance("Crowd-Control", "CrowdControl") - 0.1).abs() < 1e-9);
+    assert!(
+        table.similarity("Summon", "Surnrnon")
+            > SequenceMatcher::new("Summon", "Surnrnon").ratio() as f64
+    );
+}
+
+#[test]
+fn jamo_matcher_credits_partially_misread_syllables() {
+    assert_eq!(
+        decompose_hangul("각 가"),
+        vec!['\u{1100}', '\u{1161}', '\u{11A8}', '\u{1100}', '\u{1161}']
+    );
+    // `감속` (Slow) misread as `김속`: only the vowel of the first syllable differs.
+    let jamo: f64 = TagMatcher::Jamo.similarity("감속", "김속");
+    let syllables: f64 = TagMatcher::Difflib.similarity("감속", "김속");
+    assert!(jamo > syllables);
+    assert_eq!(TagMatcher::Jamo.similarity("메딕 타입", "메딕타입"), 1.0);
+}
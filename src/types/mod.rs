@@ -1,2 +1,26 @@
 pub(crate) mod tag;
-pub(crate) mod errors;
\ No newline at end of file
+pub(crate) mod errors;
+pub(crate) mod ocr;
+pub(crate) mod overview;
+pub(crate) mod timer;
+pub(crate) mod scan;
+pub(crate) mod template;
+pub(crate) mod resources;
+pub(crate) mod config;
+pub(crate) mod perspective;
+pub(crate) mod video;
+pub(crate) mod screen;
+pub(crate) mod preprocess;
+pub(crate) mod operators;
+pub(crate) mod recruit_result;
+pub(crate) mod refresh;
+#[cfg(feature = "tesseract")]
+pub(crate) mod tesseract;
+pub(crate) mod classifier;
+#[cfg(feature = "onnx")]
+pub(crate) mod onnx;
+pub(crate) mod pool;
+pub(crate) mod cache;
+pub(crate) mod assignment;
+pub(crate) mod matcher;
+pub(crate) mod language;
\ No newline at end of file
@@ -0,0 +1,362 @@
+use super::language::ServerLanguage;
+use opencv::{
+    core::{Mat, Rect},
+    imgproc,
+    prelude::MatTraitConst,
+};
+
+pub(crate) static TAG_WHITELIST: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ-";
+pub(crate) static SLOT_WHITELIST: &str =
+    "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789:";
+pub(crate) static DIGIT_WHITELIST: &str = "0123456789";
+
+/// Returns whether `chars` is the tag whitelist of a server language or a combination of
+/// them, i.e. whether the text about to be read is a tag. Backends that only recognize tags
+/// read nothing else.
+pub(crate) fn is_tag_whitelist(chars: &str) -> bool {
+    let all: String = ServerLanguage::combined_tag_whitelist(&ServerLanguage::ALL);
+    chars.chars().all(|c: char| all.contains(c))
+        && ServerLanguage::ALL.iter().any(|language: &ServerLanguage| {
+            language.tag_whitelist().chars().all(|c: char| chars.contains(c))
+        })
+}
+
+/// The recognition engines retag can be configured to use.
+///
+/// # Variants
+/// - `Tesseract`: General-purpose OCR with Tesseract. Needs the `tesseract` feature.
+/// - `Templates`: The `TemplateClassifier`, which compares tag crops against a library of
+///   labeled reference crops. It only recognizes tags.
+/// - `Onnx`: The `OnnxClassifier`, a neural network that classifies tag crops. It only
+///   recognizes tags and needs the `onnx` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OcrEngine {
+    Tesseract,
+    Templates,
+    Onnx,
+}
+
+impl Default for OcrEngine {
+    fn default() -> Self {
+        if cfg!(feature = "tesseract") {
+            OcrEngine::Tesseract
+        } else {
+            OcrEngine::Templates
+        }
+    }
+}
+
+impl std::str::FromStr for OcrEngine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tesseract" => Ok(OcrEngine::Tesseract),
+            "templates" => Ok(OcrEngine::Templates),
+            "onnx" => Ok(OcrEngine::Onnx),
+            _ => Err(format!("unknown OCR engine `{}`", s)),
+        }
+    }
+}
+
+/// How an `OcrBackend` lays out the text of a crop before reading it.
+///
+/// The variants correspond to the Tesseract page segmentation modes of the same name.
+///
+/// # Variants
+/// - `Auto`: Fully automatic layout analysis. This is the Tesseract default.
+/// - `SingleBlock`: The crop is a single uniform block of text.
+/// - `SingleLine`: The crop is a single line of text.
+/// - `SingleWord`: The crop is a single word.
+/// - `RawLine`: The crop is a single line, read without any Tesseract-specific layout hacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageSegMode {
+    #[default]
+    Auto,
+    SingleBlock,
+    SingleLine,
+    SingleWord,
+    RawLine,
+}
+
+impl PageSegMode {
+    /// Returns the value of the Tesseract `tessedit_pageseg_mode` variable for the mode.
+    pub fn tesseract_value(self) -> i32 {
+        match self {
+            PageSegMode::Auto => 3,
+            PageSegMode::SingleBlock => 6,
+            PageSegMode::SingleLine => 7,
+            PageSegMode::SingleWord => 8,
+            PageSegMode::RawLine => 13,
+        }
+    }
+}
+
+impl std::str::FromStr for PageSegMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(PageSegMode::Auto),
+            "single_block" => Ok(PageSegMode::SingleBlock),
+            "single_line" => Ok(PageSegMode::SingleLine),
+            "single_word" => Ok(PageSegMode::SingleWord),
+            "raw_line" => Ok(PageSegMode::RawLine),
+            _ => Err(format!("unknown page segmentation mode `{}`", s)),
+        }
+    }
+}
+
+/// The recognition engine Tesseract runs internally.
+///
+/// # Variants
+/// - `Legacy`: The original pattern-matching engine, which makes most use of user words.
+/// - `Lstm`: The neural network engine.
+/// - `Combined`: Both engines, with the results combined.
+/// - `Default`: Whatever the trained data supports, usually the LSTM engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EngineMode {
+    Legacy,
+    Lstm,
+    Combined,
+    #[default]
+    Default,
+}
+
+impl EngineMode {
+    /// Returns the Tesseract `OcrEngineMode` value of the mode.
+    pub fn tesseract_value(self) -> u32 {
+        match self {
+            EngineMode::Legacy => 0,
+            EngineMode::Lstm => 1,
+            EngineMode::Combined => 2,
+            EngineMode::Default => 3,
+        }
+    }
+}
+
+impl std::str::FromStr for EngineMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "legacy" => Ok(EngineMode::Legacy),
+            "lstm" => Ok(EngineMode::Lstm),
+            "combined" => Ok(EngineMode::Combined),
+            "default" => Ok(EngineMode::Default),
+            _ => Err(format!("unknown engine mode `{}`", s)),
+        }
+    }
+}
+
+/// A single reading of a crop produced by an `OcrBackend`.
+///
+/// # Fields
+/// - `text`: The recognized text.
+/// - `confidence`: How sure the backend is about `text`, in `[0, 1]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcrCandidate {
+    pub text: String,
+    pub confidence: f64,
+}
+
+/// A text recognition engine.
+///
+/// The recognizers only hand grayscale crops to the backend and work with the returned
+/// candidates, so Tesseract can be replaced by another engine or by a scripted backend in
+/// tests.
+pub trait OcrBackend {
+    /// Restricts the characters the backend is allowed to emit.
+    ///
+    /// Every recognizer sets the whitelist it needs before reading its regions, because one
+    /// backend instance is shared between the tag, timer and overview recognizers. Backends
+    /// that cannot restrict their output ignore the whitelist.
+    ///
+    /// # Errors
+    /// Returns an error if the backend rejects the whitelist.
+    fn set_whitelist(&mut self, chars: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Sets how the backend lays out the text of the crops it reads.
+    ///
+    /// The mode stays in effect until it is set again, so callers that change it restore
+    /// `PageSegMode::default()` afterwards. Backends without layout analysis, such as the
+    /// classifiers, ignore it, which the default implementation does.
+    ///
+    /// # Errors
+    /// Returns an error if the backend rejects the mode.
+    fn set_page_seg_mode(&mut self, _mode: PageSegMode) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Switches the backend to the trained data of a server language.
+    ///
+    /// Scans call this first, so that one backend can serve screenshots of several servers.
+    /// Switching may reset the whitelist and the page segmentation mode, so both are set
+    /// after it. Backends that do not depend on the language ignore it, which the default
+    /// implementation does.
+    ///
+    /// # Errors
+    /// Returns an error if the data of the language cannot be loaded.
+    fn set_language(
+        &mut self,
+        _language: ServerLanguage,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Switches the backend to the trained data of several server languages at once, for
+    /// reading text whose language is not known yet.
+    ///
+    /// Like `set_language`, it is followed by setting the whitelist and the page segmentation
+    /// mode. The default implementation switches to a single language with `set_language` and
+    /// ignores combinations, which suits backends that cannot load several languages.
+    ///
+    /// # Errors
+    /// Returns an error if the data of a language cannot be loaded.
+    fn set_languages(
+        &mut self,
+        languages: &[ServerLanguage],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match languages {
+            [language] => self.set_language(*language),
+            _ => Ok(()),
+        }
+    }
+
+    /// Recognizes the text in a single channel crop.
+    ///
+    /// # Returns
+    /// - `Result<Vec<OcrCandidate>, Box<dyn std::error::Error>>`: The readings of the crop,
+    ///   best first. The list is empty if nothing was recognized.
+    ///
+    /// # Errors
+    /// Returns an error if the crop cannot be handed to the engine or recognition fails.
+    fn recognize(
+        &mut self,
+        grayscale: &Mat,
+    ) -> Result<Vec<OcrCandidate>, Box<dyn std::error::Error>>;
+
+    /// Recognizes several crops, returning the readings of each crop in the order of `crops`.
+    ///
+    /// The default implementation recognizes the crops one after another. Backends that can
+    /// work on several crops at once, such as the `OcrPool`, override it.
+    ///
+    /// # Errors
+    /// Returns an error if recognizing any of the crops fails.
+    fn recognize_batch(
+        &mut self,
+        crops: &[Mat],
+    ) -> Result<Vec<Vec<OcrCandidate>>, Box<dyn std::error::Error>> {
+        crops
+            .iter()
+            .map(|crop: &Mat| self.recognize(crop))
+            .collect()
+    }
+}
+
+/// Returns the text of the most confident candidate with surrounding whitespace trimmed, or an
+/// empty string if there is none.
+pub(crate) fn best_text(candidates: &[OcrCandidate]) -> String {
+    candidates
+        .iter()
+        .max_by(|a: &&OcrCandidate, b: &&OcrCandidate| a.confidence.total_cmp(&b.confidence))
+        .map_or(String::new(), |candidate: &OcrCandidate| {
+            candidate.text.trim().to_string()
+        })
+}
+
+/// Reads the text inside a region of a grayscale image.
+///
+/// The region is thresholded with `thresh` before it is handed to Tesseract. Set `invert` for
+/// regions with dark text on a bright background so the backend always sees the same polarity.
+///
+/// # Parameters
+/// - `backend`: The OCR backend used for recognition.
+/// - `grayscale`: The single channel image that contains the region.
+/// - `rect`: The region of interest inside `grayscale`.
+/// - `thresh`: The binary threshold applied to the region.
+/// - `invert`: Whether the thresholded region is inverted.
+///
+/// # Returns
+/// - `Result<String, Box<dyn std::error::Error>>`: The recognized text with surrounding
+///   whitespace trimmed.
+///
+/// # Errors
+/// Returns an error if the region lies outside the image or if OCR fails.
+pub(crate) fn read_region(
+    backend: &mut dyn OcrBackend,
+    grayscale: &Mat,
+    rect: &Rect,
+    thresh: f64,
+    invert: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let cropped: opencv::boxed_ref::BoxedRef<'_, Mat> = grayscale.roi(*rect)?;
+    let mut threshed: Mat = Mat::default();
+    imgproc::threshold(
+        &cropped,
+        &mut threshed,
+        thresh,
+        255.0,
+        if invert {
+            imgproc::THRESH_BINARY_INV
+        } else {
+            imgproc::THRESH_BINARY
+        },
+    )?;
+    Ok(best_text(&backend.recognize(&threshed)?))
+}
+
+/// Converts a rectangle given in fractions of the image size into pixel coordinates.
+///
+/// # Parameters
+/// - `size`: The size of the image the rectangle refers to.
+/// - `relative`: `(x, y, width, height)`, each as a fraction of the image size.
+pub(crate) fn relative_rect(size: opencv::core::Size, relative: (f64, f64, f64, f64)) -> Rect {
+    Rect::new(
+        (size.width as f64 * relative.0) as i32,
+        (size.height as f64 * relative.1) as i32,
+        (size.width as f64 * relative.2) as i32,
+        (size.height as f64 * relative.3) as i32,
+    )
+}
+
+/// A deterministic backend for tests that returns scripted readings in turn, regardless of the
+/// crop it is given.
+#[cfg(test)]
+pub(crate) struct MockBackend {
+    readings: Vec<String>,
+    next: usize,
+}
+
+#[cfg(test)]
+impl MockBackend {
+    /// Creates a backend that cycles through `readings`, each with full confidence.
+    pub(crate) fn new(readings: &[&str]) -> Self {
+        MockBackend {
+            readings: readings.iter().map(|r: &&str| r.to_string()).collect(),
+            next: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+impl OcrBackend for MockBackend {
+    fn set_whitelist(&mut self, _chars: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn recognize(
+        &mut self,
+        _grayscale: &Mat,
+    ) -> Result<Vec<OcrCandidate>, Box<dyn std::error::Error>> {
+        if self.readings.is_empty() {
+            return Ok(vec![]);
+        }
+        let text: String = self.readings[self.next % self.readings.len()].clone();
+        self.next += 1;
+        Ok(vec![OcrCandidate {
+            text,
+            confidence: 1.0,
+        }])
+    }
+}
@@ -0,0 +1,306 @@
+use super::{
+    language::ServerLanguage,
+    ocr::{self, OcrBackend, OcrCandidate},
+    tag::TAGS_STRINGS,
+};
+use opencv::{
+    core::{Mat, Size, CV_8UC1},
+    imgproc,
+    prelude::*,
+};
+use std::path::Path;
+use tract_onnx::prelude::*;
+
+/// Size tag crops are resized to before they are fed to the network, as `(width, height)`.
+static MODEL_INPUT_SIZE: (usize, usize) = (128, 32);
+/// Label of the extra class for crops that do not show a tag.
+static NOT_A_TAG: &str = "not a tag";
+/// Minimum probability for a tag to be reported as a candidate.
+static MIN_CANDIDATE_PROBABILITY: f64 = 0.1;
+static MAX_CANDIDATES: usize = 3;
+
+/// A tag recognizer that classifies tag crops with a small convolutional network.
+///
+/// The model is an ONNX file run on the CPU with tract. It takes a `1 x 1 x 32 x 128` tensor
+/// holding the preprocessed grayscale crop scaled to `[0, 1]`, and returns 29 logits: one per
+/// entry of `TAGS_STRINGS`, in that order, followed by `NOT_A_TAG`. It is meant for blurred,
+/// compressed or photographed tags that OCR misreads. Predictions are reported as the tag
+/// strings of the language set with `set_language`.
+///
+/// Only tag crops can be classified. While the active whitelist is not a tag whitelist (see
+/// `ocr::is_tag_whitelist`), `recognize` returns no candidates, so the timer, counter, slot and
+/// name readers find nothing instead of a tag name.
+pub struct OnnxClassifier {
+    model: TypedRunnableModel<TypedModel>,
+    reading_tags: bool,
+    language: ServerLanguage,
+}
+
+impl OnnxClassifier {
+    /// Loads and optimizes the model.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, is not a valid ONNX model or does not
+    /// accept the expected input shape.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let (width, height) = MODEL_INPUT_SIZE;
+        let model: TypedRunnableModel<TypedModel> = tract_onnx::onnx()
+            .model_for_path(path)?
+            .with_input_fact(0, f32::fact([1, 1, height, width]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+        Ok(OnnxClassifier {
+            model,
+            reading_tags: false,
+            language: ServerLanguage::default(),
+        })
+    }
+
+    /// Returns the probability of every class for a preprocessed tag crop.
+    ///
+    /// # Returns
+    /// - `Result<Vec<(&'static str, f64)>, Box<dyn std::error::Error>>`: The entries of
+    ///   `TAGS_STRINGS` followed by `NOT_A_TAG`, each with its probability. The probabilities
+    ///   sum to 1.
+    ///
+    /// # Errors
+    /// Returns an error if the crop is not 8-bit single channel or cannot be resized, or if the
+    /// model output does not have one value per class.
+    pub fn probabilities(
+        &self,
+        grayscale: &Mat,
+    ) -> Result<Vec<(&'static str, f64)>, Box<dyn std::error::Error>> {
+        if grayscale.typ() != CV_8UC1 {
+            return Err("tag model crops must be 8-bit single channel images".into());
+        }
+        let (width, height) = MODEL_INPUT_SIZE;
+        let mut resized: Mat = Mat::default();
+        imgproc::resize(
+            grayscale,
+            &mut resized,
+            Size::new(width as i32, height as i32),
+            0.0,
+            0.0,
+            imgproc::INTER_AREA,
+        )?;
+        let pixels: &[u8] = resized.data_bytes()?;
+        let input: Tensor =
+            tract_ndarray::Array4::from_shape_fn((1, 1, height, width), |(_, _, y, x)| {
+                pixels[y * width + x] as f32 / 255.0
+            })
+            .into();
+
+        let outputs: TVec<TValue> = self.model.run(tvec!(input.into()))?;
+        let logits: Vec<f64> = outputs[0]
+            .to_array_view::<f32>()?
+            .iter()
+            .map(|logit: &f32| *logit as f64)
+            .collect();
+        let labels: Vec<&'static str> = TAGS_STRINGS.iter().copied().chain([NOT_A_TAG]).collect();
+        if logits.len() != labels.len() {
+            return Err(format!(
+                "tag model returned {} classes, expected {}",
+                logits.len(),
+                labels.len()
+            )
+            .into());
+        }
+
+        let max: f64 = logits.iter().copied().fold(f64::MIN, f64::max);
+        let exp: Vec<f64> = logits
+            .iter()
+            .map(|logit: &f64| (logit - max).exp())
+            .collect();
+        let sum: f64 = exp.iter().sum();
+        Ok(labels
+            .into_iter()
+            .zip(exp)
+            .map(|(label, e): (&'static str, f64)| (label, e / sum))
+            .collect())
+    }
+}
+
+impl OcrBackend for OnnxClassifier {
+    /// Remembers whether the whitelist is a tag whitelist.
+    fn set_whitelist(&mut self, chars: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.reading_tags = ocr::is_tag_whitelist(chars);
+        Ok(())
+    }
+
+    /// Remembers the language whose tag strings are reported.
+    fn set_language(&mut self, language: ServerLanguage) -> Result<(), Box<dyn std::error::Error>> {
+        self.language = language;
+        Ok(())
+    }
+
+    /// Classifies a preprocessed tag crop.
+    ///
+    /// The most probable tags with at least `MIN_CANDIDATE_PROBABILITY` are returned as tag
+    /// strings of the active language with their probability as confidence, best first. If
+    /// `NOT_A_TAG` is the most probable class or the active whitelist is not a tag whitelist,
+    /// no candidate is returned.
+    fn recognize(
+        &mut self,
+        grayscale: &Mat,
+    ) -> Result<Vec<OcrCandidate>, Box<dyn std::error::Error>> {
+        if !self.reading_tags {
+            return Ok(vec![]);
+        }
+        let mut probabilities: Vec<(&'static str, f64)> = self.probabilities(grayscale)?;
+        probabilities.sort_by(|a: &(&str, f64), b: &(&str, f64)| b.1.total_cmp(&a.1));
+        if probabilities
+            .first()
+            .is_some_and(|(label, _)| *label == NOT_A_TAG)
+        {
+            return Ok(vec![]);
+        }
+        Ok(probabilities
+            .into_iter()
+            .filter(|(label, p): &(&str, f64)| {
+                *label != NOT_A_TAG && *p >= MIN_CANDIDATE_PROBABILITY
+            })
+            .take(MAX_CANDIDATES)
+            .filter_map(|(label, p): (&str, f64)| {
+                let index: usize = TAGS_STRINGS.iter().position(|s: &&str| *s == label)?;
+                Some(OcrCandidate {
+                    text: self.language.tag_strings()[index].to_string(),
+                    confidence: p,
+                })
+            })
+            .collect())
+    }
+}
+
+/// Appends a base-128 varint to a protobuf message.
+#[cfg(test)]
+fn proto_varint(message: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        message.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    message.push(value as u8);
+}
+
+/// Appends a varint field to a protobuf message.
+#[cfg(test)]
+fn proto_int(message: &mut Vec<u8>, field: u64, value: u64) {
+    proto_varint(message, field << 3);
+    proto_varint(message, value);
+}
+
+/// Appends a length-delimited field to a protobuf message.
+#[cfg(test)]
+fn proto_bytes(message: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    proto_varint(message, (field << 3) | 2);
+    proto_varint(message, bytes.len() as u64);
+    message.extend_from_slice(bytes);
+}
+
+/// Encodes an ONNX model mapping a `1 x 1 x 32 x 128` crop to `classes` logits: the mean
+/// pixel value times 5 for the first class and 0 for the others.
+#[cfg(test)]
+fn tiny_model(classes: usize) -> Vec<u8> {
+    let value_info = |name: &str, shape: &[u64]| -> Vec<u8> {
+        let mut shape_proto: Vec<u8> = vec![];
+        for dim in shape {
+            let mut dimension: Vec<u8> = vec![];
+            proto_int(&mut dimension, 1, *dim);
+            proto_bytes(&mut shape_proto, 1, &dimension);
+        }
+        let mut tensor_type: Vec<u8> = vec![];
+        proto_int(&mut tensor_type, 1, 1);
+        proto_bytes(&mut tensor_type, 2, &shape_proto);
+        let mut type_proto: Vec<u8> = vec![];
+        proto_bytes(&mut type_proto, 1, &tensor_type);
+        let mut info: Vec<u8> = vec![];
+        proto_bytes(&mut info, 1, name.as_bytes());
+        proto_bytes(&mut info, 2, &type_proto);
+        info
+    };
+    let node = |op_type: &str, inputs: &[&str], output: &str, axis: Option<u64>| -> Vec<u8> {
+        let mut node: Vec<u8> = vec![];
+        for input in inputs {
+            proto_bytes(&mut node, 1, input.as_bytes());
+        }
+        proto_bytes(&mut node, 2, output.as_bytes());
+        proto_bytes(&mut node, 4, op_type.as_bytes());
+        if let Some(axis) = axis {
+            let mut attribute: Vec<u8> = vec![];
+            proto_bytes(&mut attribute, 1, b"axis");
+            proto_int(&mut attribute, 3, axis);
+            proto_int(&mut attribute, 20, 2);
+            proto_bytes(&mut node, 5, &attribute);
+        }
+        node
+    };
+
+    let mut weights: Vec<u8> = vec![];
+    proto_int(&mut weights, 1, 1);
+    proto_int(&mut weights, 1, classes as u64);
+    proto_int(&mut weights, 2, 1);
+    proto_bytes(&mut weights, 8, b"weights");
+    let raw: Vec<u8> = (0..classes)
+        .flat_map(|class: usize| (if class == 0 { 5.0f32 } else { 0.0f32 }).to_le_bytes())
+        .collect();
+    proto_bytes(&mut weights, 9, &raw);
+
+    let mut graph: Vec<u8> = vec![];
+    proto_bytes(&mut graph, 1, &node("GlobalAveragePool", &["input"], "pooled", None));
+    proto_bytes(&mut graph, 1, &node("Flatten", &["pooled"], "flat", Some(1)));
+    proto_bytes(&mut graph, 1, &node("MatMul", &["flat", "weights"], "logits", None));
+    proto_bytes(&mut graph, 2, b"tiny");
+    proto_bytes(&mut graph, 5, &weights);
+    proto_bytes(&mut graph, 11, &value_info("input", &[1, 1, 32, 128]));
+    proto_bytes(&mut graph, 12, &value_info("logits", &[1, classes as u64]));
+
+    let mut opset: Vec<u8> = vec![];
+    proto_int(&mut opset, 2, 13);
+    let mut model: Vec<u8> = vec![];
+    proto_int(&mut model, 1, 8);
+    proto_bytes(&mut model, 7, &graph);
+    proto_bytes(&mut model, 8, &opset);
+    model
+}
+
+#[test]
+fn onnx_classifier_loads_model_and_checks_class_count() -> Result<(), Box<dyn std::error::Error>> {
+    let path: std::path::PathBuf =
+        std::env::temp_dir().join(format!("retag-tag-model-{}.onnx", std::process::id()));
+    let white: Mat = Mat::new_rows_cols_with_default(
+        40,
+        160,
+        opencv::core::CV_8UC1,
+        opencv::core::Scalar::all(255.0),
+    )?;
+    std::fs::write(&path, tiny_model(TAGS_STRINGS.len() + 1))?;
+    let classifier: Result<OnnxClassifier, Box<dyn std::error::Error>> =
+        OnnxClassifier::load(&path);
+    std::fs::write(&path, tiny_model(10))?;
+    let wrong_class_count: Result<OnnxClassifier, Box<dyn std::error::Error>> =
+        OnnxClassifier::load(&path);
+    std::fs::remove_file(&path)?;
+
+    let mut classifier: OnnxClassifier = classifier?;
+    let probabilities: Vec<(&'static str, f64)> = classifier.probabilities(&white)?;
+    assert_eq!(probabilities.len(), TAGS_STRINGS.len() + 1);
+    assert!((probabilities.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-6);
+    assert!(wrong_class_count?.probabilities(&white).is_err());
+    let color: Mat = Mat::new_rows_cols_with_default(
+        40,
+        160,
+        opencv::core::CV_8UC3,
+        opencv::core::Scalar::all(255.0),
+    )?;
+    assert!(classifier.probabilities(&color).is_err());
+
+    classifier.set_whitelist(ocr::DIGIT_WHITELIST)?;
+    assert!(classifier.recognize(&white)?.is_empty());
+    classifier.set_whitelist(ocr::TAG_WHITELIST)?;
+    let candidates: Vec<OcrCandidate> = classifier.recognize(&white)?;
+    assert_eq!(candidates[0].text, TAGS_STRINGS[0]);
+    classifier.set_language(ServerLanguage::Japanese)?;
+    classifier.set_whitelist(&ServerLanguage::Japanese.tag_whitelist())?;
+    let candidates: Vec<OcrCandidate> = classifier.recognize(&white)?;
+    assert_eq!(candidates[0].text, ServerLanguage::Japanese.tag_strings()[0]);
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use difflib::sequencematcher::SequenceMatcher;
+use std::{fs, path::Path};
+
+/// Operators obtainable through recruitment, as `(name, rarity)`.
+static RECRUITABLE_OPERATORS: [(&str, u8); 118] = [
+    ("Lancet-2", 1),
+    ("Castle-3", 1),
+    ("THRM-EX", 1),
+    ("Justice Knight", 1),
+    ("Yato", 2),
+    ("Noir Corne", 2),
+    ("Rangers", 2),
+    ("Durin", 2),
+    ("12F", 2),
+    ("Fang", 3),
+    ("Vanilla", 3),
+    ("Plume", 3),
+    ("Melantha", 3),
+    ("Popukar", 3),
+    ("Cardigan", 3),
+    ("Beagle", 3),
+    ("Spot", 3),
+    ("Kroos", 3),
+    ("Adnachiel", 3),
+    ("Catapult", 3),
+    ("Lava", 3),
+    ("Midnight", 3),
+    ("Steward", 3),
+    ("Ansel", 3),
+    ("Hibiscus", 3),
+    ("Orchid", 3),
+    ("Myrtle", 4),
+    ("Courier", 4),
+    ("Scavenger", 4),
+    ("Vigna", 4),
+    ("Dobermann", 4),
+    ("Matoimaru", 4),
+    ("Mousse", 4),
+    ("Frostleaf", 4),
+    ("Estelle", 4),
+    ("Beehunter", 4),
+    ("Utage", 4),
+    ("Cutter", 4),
+    ("Conviction", 4),
+    ("Gravel", 4),
+    ("Rope", 4),
+    ("Shaw", 4),
+    ("Jessica", 4),
+    ("May", 4),
+    ("Meteor", 4),
+    ("Shirayuki", 4),
+    ("Aciddrop", 4),
+    ("Ambriel", 4),
+    ("Pinecone", 4),
+    ("Haze", 4),
+    ("Gitano", 4),
+    ("Greyy", 4),
+    ("Jaye", 4),
+    ("Perfumer", 4),
+    ("Myrrh", 4),
+    ("Sussurro", 4),
+    ("Purestream", 4),
+    ("Gummy", 4),
+    ("Cuora", 4),
+    ("Matterhorn", 4),
+    ("Dur-nar", 4),
+    ("Bubble", 4),
+    ("Earthspirit", 4),
+    ("Deepcolor", 4),
+    ("Podenco", 4),
+    ("Click", 4),
+    ("Vermeil", 4),
+    ("Beanstalk", 4),
+    ("Texas", 5),
+    ("Zima", 5),
+    ("Projekt Red", 5),
+    ("Indra", 5),
+    ("Specter", 5),
+    ("Lappland", 5),
+    ("Whislash", 5),
+    ("Blue Poison", 5),
+    ("Platinum", 5),
+    ("Meteorite", 5),
+    ("Executor", 5),
+    ("Andreana", 5),
+    ("Firewatch", 5),
+    ("Provence", 5),
+    ("Liskarm", 5),
+    ("Nearl", 5),
+    ("Vulcan", 5),
+    ("Croissant", 5),
+    ("Hung", 5),
+    ("Manticore", 5),
+    ("FEater", 5),
+    ("Cliffheart", 5),
+    ("Kafka", 5),
+    ("Mayer", 5),
+    ("Swire", 5),
+    ("Glaucus", 5),
+    ("Shamare", 5),
+    ("Warfarin", 5),
+    ("Silence", 5),
+    ("Ptilopsis", 5),
+    ("Breeze", 5),
+    ("Istina", 5),
+    ("Pramanix", 5),
+    ("Tsukinogi", 5),
+    ("Astesia", 5),
+    ("Sesa", 5),
+    ("Broca", 5),
+    ("Exusiai", 6),
+    ("Siege", 6),
+    ("Ifrit", 6),
+    ("Eyjafjalla", 6),
+    ("Angelina", 6),
+    ("Shining", 6),
+    ("Nightingale", 6),
+    ("Hoshiguma", 6),
+    ("SilverAsh", 6),
+    ("Saga", 6),
+    ("Blaze", 6),
+    ("Aak", 6),
+    ("Ceobe", 6),
+];
+
+/// Minimum similarity for an OCR reading to be accepted as an operator name.
+static NAME_ACCEPT_THRESH: f64 = 0.5;
+
+/// The operators an OCR reading of a name is matched against.
+///
+/// The built-in database holds the recruitment pool. A database file can replace it, e.g. to
+/// follow pool updates without recompiling. Every non-empty line of the file holds
+/// `name,rarity`; lines starting with `#` are ignored.
+#[derive(Debug, Clone)]
+pub struct OperatorDatabase {
+    operators: Vec<(String, u8)>,
+}
+
+impl Default for OperatorDatabase {
+    fn default() -> Self {
+        OperatorDatabase {
+            operators: RECRUITABLE_OPERATORS
+                .iter()
+                .map(|(name, rarity)| (name.to_string(), *rarity))
+                .collect(),
+        }
+    }
+}
+
+impl OperatorDatabase {
+    /// Reads an operator database file.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or a line is not a `name,rarity` pair.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut operators: Vec<(String, u8)> = vec![];
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, rarity) = line
+                .rsplit_once(',')
+                .ok_or_else(|| format!("line {} is not a `name,rarity` pair", index + 1))?;
+            operators.push((name.trim().to_string(), rarity.trim().parse::<u8>()?));
+        }
+        Ok(OperatorDatabase { operators })
+    }
+
+    /// Finds the operator whose name is most similar to an OCR reading.
+    ///
+    /// # Returns
+    /// - `Option<(&str, u8, f64)>`: The operator name, its rarity and the similarity ratio, or
+    ///   `None` if no name reaches `NAME_ACCEPT_THRESH`.
+    pub fn best_match(&self, text: &str) -> Option<(&str, u8, f64)> {
+        let text: String = text.to_lowercase();
+        self.operators
+            .iter()
+            .map(|(name, rarity)| {
+                let lower: String = name.to_lowercase();
+                let ratio: f64 = SequenceMatcher::new(lower.as_str(), text.as_str()).ratio() as f64;
+                (name.as_str(), *rarity, ratio)
+            })
+            .filter(|(_, _, ratio)| *ratio >= NAME_ACCEPT_THRESH)
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+    }
+}
+
+#[test]
+fn operator_database_loads_name_rarity_pairs() -> Result<(), Box<dyn std::error::Error>> {
+    let path: std::path::PathBuf =
+        std::env::temp_dir().join(format!("retag-operators-{}.txt", std::process::id()));
+    fs::write(&path, "# recruitment pool\n\nTexas, 5\nProjekt Red,5\n")?;
+    let database: Result<OperatorDatabase, Box<dyn std::error::Error>> =
+        OperatorDatabase::load(&path);
+    fs::write(&path, "Texas 5\n")?;
+    let malformed: Result<OperatorDatabase, Box<dyn std::error::Error>> =
+        OperatorDatabase::load(&path);
+    fs::remove_file(&path)?;
+
+    assert_eq!(
+        database?.operators,
+        vec![("Texas".to_string(), 5), ("Projekt Red".to_string(), 5)]
+    );
+    assert!(malformed.is_err());
+    Ok(())
+}
+
+#[test]
+fn best_match_forgives_misreadings_and_rejects_unknown_names() {
+    let database: OperatorDatabase = OperatorDatabase::default();
+    let (name, rarity, ratio) = database.best_match("EXUSIA1").unwrap();
+    assert_eq!((name, rarity), ("Exusiai", 6));
+    assert!(ratio < 1.0);
+    assert_eq!(database.best_match("Projekt Red").map(|(name, _, _)| name), Some("Projekt Red"));
+    assert_eq!(database.best_match("qqqqqqqq"), None);
+}
@@ -0,0 +1,219 @@
+use super::ocr::{self, OcrBackend};
+use chrono::TimeDelta;
+use opencv::{
+    core::{Mat, Rect},
+    imgproc,
+    prelude::MatTraitConst,
+};
+use std::fmt;
+
+/// Slot regions of the recruitment overview as `(x, y, width, height)` fractions of the screen.
+/// Ordered left to right, top to bottom, matching the in-game slot numbering.
+static OVERVIEW_SLOTS: [(f64, f64, f64, f64); 4] = [
+    (0.030, 0.190, 0.455, 0.360),
+    (0.515, 0.190, 0.455, 0.360),
+    (0.030, 0.580, 0.455, 0.360),
+    (0.515, 0.580, 0.455, 0.360),
+];
+static SLOT_TEXT_THRESH: f64 = 150.0;
+static LOCKED_BRIGHTNESS_THRESH: f64 = 0.12;
+
+/// The state of a single recruitment slot on the overview screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotState {
+    /// The slot has not been unlocked yet.
+    Locked,
+    /// The slot is unlocked and no recruitment is running.
+    Empty,
+    /// A recruitment is running; holds the remaining time shown on the countdown.
+    InProgress(TimeDelta),
+    /// The recruitment is finished and the operator can be hired.
+    Done,
+    /// The slot text could not be attributed to any of the other states.
+    Unknown,
+}
+
+impl fmt::Display for SlotState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Locked => write!(f, "Locked"),
+            Self::Empty => write!(f, "Empty"),
+            Self::InProgress(remaining) => write!(
+                f,
+                "In progress ({:02}:{:02}:{:02} left)",
+                remaining.num_hours(),
+                remaining.num_minutes() % 60,
+                remaining.num_seconds() % 60
+            ),
+            Self::Done => write!(f, "Done"),
+            Self::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// A recruitment slot detected on the overview screen.
+///
+/// # Fields
+/// - `index`: The zero based slot number, counted left to right, top to bottom.
+/// - `state`: The detected `SlotState`.
+/// - `bounding_box`: The region of the screenshot the slot was read from.
+#[derive(Debug)]
+pub struct RecruitmentSlot {
+    index: usize,
+    state: SlotState,
+    bounding_box: Rect,
+}
+
+impl RecruitmentSlot {
+    /// Returns the zero based slot number.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the detected state of the slot.
+    pub fn state(&self) -> SlotState {
+        self.state
+    }
+
+    /// Returns the region of the screenshot the slot was read from.
+    pub fn bounding_box(&self) -> Rect {
+        self.bounding_box
+    }
+}
+
+/// Reads the state of all four recruitment slots from a screenshot of the recruitment overview.
+///
+/// # Parameters
+/// - `image`: A reference to the full screenshot in BGR color format (e.g., CV_8UC3).
+/// - `backend`: The OCR backend used for recognition. The character whitelist is
+///   overwritten.
+///
+/// # Returns
+/// - `Result<Vec<RecruitmentSlot>, Box<dyn std::error::Error>>`: One entry per slot, ordered
+///   by slot number.
+///
+/// # Processing Steps
+/// 1. **Color Conversion**: The screenshot is converted to grayscale.
+///
+/// 2. **Locked Check**: Locked slots are rendered almost black. A slot whose mean brightness is
+///    below `LOCKED_BRIGHTNESS_THRESH` is reported as `SlotState::Locked` without running OCR.
+///
+/// 3. **Text Extraction**: The remaining slots are read with a whitelist of letters, digits and
+///    colons, and the text is mapped onto a `SlotState` by `parse_slot_text`.
+///
+/// # Example Usage
+/// ```rust
+/// let image: Mat = ...; // Load a screenshot of the recruitment overview
+/// let mut backend: TesseractBackend = ...; // Initialize the OCR backend
+/// for slot in image_to_slots(&image, &mut backend)? {
+///     println!("Slot {}: {}", slot.index() + 1, slot.state());
+/// }
+/// ```
+///
+/// # Errors
+/// This function may return errors related to image processing operations or OCR processing.
+pub fn image_to_slots(
+    image: &Mat,
+    backend: &mut dyn OcrBackend,
+) -> Result<Vec<RecruitmentSlot>, Box<dyn std::error::Error>> {
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &image,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        opencv::core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    backend.set_whitelist(ocr::SLOT_WHITELIST)?;
+
+    let mut slots: Vec<RecruitmentSlot> = vec![];
+    for (index, relative) in OVERVIEW_SLOTS.iter().enumerate() {
+        let rect: Rect = ocr::relative_rect(gray.size()?, *relative);
+        let brightness: f64 = opencv::core::mean_def(&gray.roi(rect)?)?.get(0).unwrap() / 255.0;
+        let state: SlotState = if brightness < LOCKED_BRIGHTNESS_THRESH {
+            SlotState::Locked
+        } else {
+            parse_slot_text(&ocr::read_region(backend, &gray, &rect, SLOT_TEXT_THRESH, true)?)
+        };
+        slots.push(RecruitmentSlot {
+            index,
+            state,
+            bounding_box: rect,
+        });
+    }
+    Ok(slots)
+}
+
+/// Maps the OCR text of a slot onto a `SlotState`.
+///
+/// A countdown (`HH:MM:SS`) takes precedence over any label, since running slots also show
+/// an "Expedite" button. Otherwise the slot labels "Hire", "Recruit Now" and "Locked" are
+/// matched case-insensitively.
+fn parse_slot_text(text: &str) -> SlotState {
+    if let Some(remaining) = parse_countdown(text) {
+        return SlotState::InProgress(remaining);
+    }
+    let lower: String = text.to_lowercase();
+    if lower.contains("hire") {
+        SlotState::Done
+    } else if lower.contains("recruit") {
+        SlotState::Empty
+    } else if lower.contains("lock") {
+        SlotState::Locked
+    } else {
+        SlotState::Unknown
+    }
+}
+
+/// Finds the first `HH:MM:SS` countdown in `text` and returns it as a duration.
+///
+/// The components are parsed as unsigned numbers, so negative values such as `-1:00:00` are
+/// not taken for a countdown.
+pub(crate) fn parse_countdown(text: &str) -> Option<TimeDelta> {
+    text.split_whitespace().find_map(|word: &str| {
+        let parts: Vec<u32> = word
+            .split(':')
+            .map(|part: &str| part.parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+            .ok()?;
+        match parts.as_slice() {
+            [h, m, s] if *m < 60 && *s < 60 => Some(TimeDelta::seconds(
+                *h as i64 * 3600 + *m as i64 * 60 + *s as i64,
+            )),
+            _ => None,
+        }
+    })
+}
+
+#[test]
+fn parse_slot_text_maps_every_state() {
+    let cases: [(&str, SlotState); 6] = [
+        ("Locked", SlotState::Locked),
+        ("Recruit Now", SlotState::Empty),
+        ("Hire", SlotState::Done),
+        (
+            "Expedite 08:59:30",
+            SlotState::InProgress(TimeDelta::seconds(8 * 3600 + 59 * 60 + 30)),
+        ),
+        (
+            "RECRUIT NOW 01:00:00",
+            SlotState::InProgress(TimeDelta::hours(1)),
+        ),
+        ("???", SlotState::Unknown),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(parse_slot_text(text), expected, "{}", text);
+    }
+}
+
+#[test]
+fn parse_countdown_rejects_negative_and_out_of_range_parts() {
+    assert_eq!(
+        parse_countdown("Expedite 03:20:05"),
+        Some(TimeDelta::seconds(3 * 3600 + 20 * 60 + 5))
+    );
+    assert_eq!(parse_countdown("-1:00:00"), None);
+    assert_eq!(parse_countdown("00:-5:10"), None);
+    assert_eq!(parse_countdown("01:60:00"), None);
+    assert_eq!(parse_countdown("01:00"), None);
+}
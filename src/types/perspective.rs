@@ -0,0 +1,229 @@
+use opencv::{
+    core::{self, Mat, Point, Point2f, Size, Vector},
+    imgproc,
+    prelude::*,
+};
+
+/// Width the photo is downscaled to before searching for the screen outline.
+static DETECTION_WIDTH: f64 = 1000.0;
+/// Minimum area of the screen quadrilateral relative to the photo area.
+static MIN_SCREEN_AREA: f64 = 0.2;
+static CANNY_THRESH: (f64, f64) = (50.0, 150.0);
+static MOIRE_BLUR_SIGMA: f64 = 1.2;
+static GLARE_CLAHE_CLIP_LIMIT: f64 = 2.0;
+static GLARE_CLAHE_TILES: i32 = 8;
+
+/// Turns a camera photo of a screen into an image that looks like a screenshot.
+///
+/// The screen outline is located and warped to a rectangle, then moiré patterns and glare are
+/// reduced. The result can be passed to the same recognizers as a regular screenshot.
+///
+/// # Parameters
+/// - `image`: A reference to the photo in BGR color format (e.g., CV_8UC3).
+///
+/// # Returns
+/// - `Result<Mat, Box<dyn std::error::Error>>`: The corrected image. If no screen outline is
+///   found, the photo is only cleaned up and keeps its original geometry.
+///
+/// # Processing Steps
+/// 1. **Screen Detection**: `find_screen_quad` searches for the largest convex quadrilateral.
+///
+/// 2. **Perspective Warp**: The quadrilateral is mapped onto an upright rectangle with
+///    `warp_perspective`. The output size follows the longest opposing edges of the quad.
+///
+/// 3. **Moiré Reduction**: A light Gaussian blur removes the interference pattern between the
+///    display's pixel grid and the camera sensor.
+///
+/// 4. **Glare Reduction**: CLAHE is applied to the lightness channel, which flattens bright
+///    reflections without shifting the colors used for tag selection detection.
+///
+/// # Example Usage
+/// ```rust
+/// let photo: Mat = imgcodecs::imread("photo.jpg", imgcodecs::IMREAD_COLOR_BGR)?;
+/// let screenshot: Mat = rectify_photo(&photo)?;
+/// let result: ScanResult = scan_image(&screenshot, &mut backend, &config)?;
+/// ```
+///
+/// # Errors
+/// This function may return errors related to image processing operations.
+pub fn rectify_photo(image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+    let warped: Mat = match find_screen_quad(image)? {
+        Some(quad) => {
+            let (width, height) = quad_output_size(&quad);
+            let target: Vector<Point2f> = Vector::from_slice(&[
+                Point2f::new(0.0, 0.0),
+                Point2f::new(width as f32, 0.0),
+                Point2f::new(width as f32, height as f32),
+                Point2f::new(0.0, height as f32),
+            ]);
+            let transform: Mat = imgproc::get_perspective_transform_def(&quad, &target)?;
+            let mut warped: Mat = Mat::default();
+            imgproc::warp_perspective_def(
+                image,
+                &mut warped,
+                &transform,
+                Size::new(width, height),
+            )?;
+            warped
+        }
+        None => image.try_clone()?,
+    };
+    reduce_moire_and_glare(&warped)
+}
+
+/// Finds the corners of the screen in a photo.
+///
+/// # Returns
+/// - `Result<Option<Vector<Point2f>>, Box<dyn std::error::Error>>`: The corners in the order
+///   top-left, top-right, bottom-right, bottom-left in full resolution coordinates, or `None`
+///   if no quadrilateral covering at least `MIN_SCREEN_AREA` of the photo is found.
+fn find_screen_quad(image: &Mat) -> Result<Option<Vector<Point2f>>, Box<dyn std::error::Error>> {
+    let scale: f64 = (DETECTION_WIDTH / image.cols() as f64).min(1.0);
+    let mut small: Mat = Mat::default();
+    imgproc::resize(
+        image,
+        &mut small,
+        Size::default(),
+        scale,
+        scale,
+        imgproc::INTER_AREA,
+    )?;
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &small,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    let mut blurred: Mat = Mat::default();
+    imgproc::gaussian_blur_def(&gray, &mut blurred, Size::new(5, 5), 0.0)?;
+    let mut edges: Mat = Mat::default();
+    imgproc::canny_def(&blurred, &mut edges, CANNY_THRESH.0, CANNY_THRESH.1)?;
+    let mut closed: Mat = Mat::default();
+    imgproc::dilate_def(&edges, &mut closed, &Mat::default())?;
+
+    let mut contours: Vector<Vector<Point>> = Vector::new();
+    imgproc::find_contours_def(
+        &closed,
+        &mut contours,
+        imgproc::RETR_EXTERNAL,
+        imgproc::CHAIN_APPROX_SIMPLE,
+    )?;
+    let min_area: f64 = MIN_SCREEN_AREA * small.size()?.area() as f64;
+    let mut best: Option<(f64, Vector<Point>)> = None;
+    for contour in contours.iter() {
+        let area: f64 = imgproc::contour_area_def(&contour)?;
+        if area < min_area || best.as_ref().is_some_and(|(best_area, _)| *best_area >= area) {
+            continue;
+        }
+        let perimeter: f64 = imgproc::arc_length(&contour, true)?;
+        let mut poly: Vector<Point> = Vector::new();
+        imgproc::approx_poly_dp(&contour, &mut poly, 0.02 * perimeter, true)?;
+        if poly.len() == 4 && imgproc::is_contour_convex(&poly)? {
+            best = Some((area, poly));
+        }
+    }
+
+    Ok(best.map(|(_, poly)| {
+        let corners: Vec<Point2f> = poly
+            .iter()
+            .map(|p: Point| Point2f::new((p.x as f64 / scale) as f32, (p.y as f64 / scale) as f32))
+            .collect();
+        order_corners(&corners)
+    }))
+}
+
+/// Orders four corners as top-left, top-right, bottom-right, bottom-left.
+///
+/// The corners are sorted clockwise by their angle around the centroid, which keeps every
+/// corner exactly once even for quads rotated by about 45°. The sequence then starts at the
+/// corner with the smallest `x + y`.
+fn order_corners(corners: &[Point2f]) -> Vector<Point2f> {
+    let count: f32 = corners.len() as f32;
+    let cx: f32 = corners.iter().map(|p: &Point2f| p.x).sum::<f32>() / count;
+    let cy: f32 = corners.iter().map(|p: &Point2f| p.y).sum::<f32>() / count;
+    let mut sorted: Vec<Point2f> = corners.to_vec();
+    // With y pointing down, increasing angles run clockwise on screen.
+    sorted.sort_by(|a: &Point2f, b: &Point2f| {
+        (a.y - cy)
+            .atan2(a.x - cx)
+            .total_cmp(&(b.y - cy).atan2(b.x - cx))
+    });
+    let start: usize = (0..sorted.len())
+        .min_by(|&a: &usize, &b: &usize| {
+            (sorted[a].x + sorted[a].y).total_cmp(&(sorted[b].x + sorted[b].y))
+        })
+        .unwrap_or(0);
+    sorted.rotate_left(start);
+    Vector::from_slice(&sorted)
+}
+
+/// Computes the size of the rectified image from the longest opposing edges of the quad.
+fn quad_output_size(quad: &Vector<Point2f>) -> (i32, i32) {
+    let corners: Vec<Point2f> = quad.to_vec();
+    let length = |a: Point2f, b: Point2f| ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt();
+    let width: f32 = length(corners[0], corners[1]).max(length(corners[3], corners[2]));
+    let height: f32 = length(corners[0], corners[3]).max(length(corners[1], corners[2]));
+    (width.round() as i32, height.round() as i32)
+}
+
+/// Reduces moiré patterns and glare in a rectified photo.
+fn reduce_moire_and_glare(image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+    let mut smoothed: Mat = Mat::default();
+    imgproc::gaussian_blur_def(image, &mut smoothed, Size::new(0, 0), MOIRE_BLUR_SIGMA)?;
+
+    let mut lab: Mat = Mat::default();
+    imgproc::cvt_color(
+        &smoothed,
+        &mut lab,
+        imgproc::COLOR_BGR2Lab,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    let mut channels: Vector<Mat> = Vector::new();
+    core::split(&lab, &mut channels)?;
+    let mut clahe: core::Ptr<imgproc::CLAHE> = imgproc::create_clahe(
+        GLARE_CLAHE_CLIP_LIMIT,
+        Size::new(GLARE_CLAHE_TILES, GLARE_CLAHE_TILES),
+    )?;
+    let mut lightness: Mat = Mat::default();
+    clahe.apply(&channels.get(0)?, &mut lightness)?;
+    channels.set(0, lightness)?;
+    core::merge(&channels, &mut lab)?;
+
+    let mut corrected: Mat = Mat::default();
+    imgproc::cvt_color(
+        &lab,
+        &mut corrected,
+        imgproc::COLOR_Lab2BGR,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    Ok(corrected)
+}
+
+#[test]
+fn order_corners_keeps_every_corner_of_rotated_quads() {
+    let ordered = |corners: &[(f32, f32)]| -> Vec<(f32, f32)> {
+        let points: Vec<Point2f> = corners
+            .iter()
+            .map(|(x, y): &(f32, f32)| Point2f::new(*x, *y))
+            .collect();
+        order_corners(&points)
+            .iter()
+            .map(|p: Point2f| (p.x, p.y))
+            .collect()
+    };
+    assert_eq!(
+        ordered(&[(90.0, 60.0), (10.0, 5.0), (5.0, 70.0), (100.0, 0.0)]),
+        vec![(10.0, 5.0), (100.0, 0.0), (90.0, 60.0), (5.0, 70.0)]
+    );
+    // A quad rotated by 45°: sums and differences tie, angles do not.
+    let diamond: Vec<(f32, f32)> =
+        ordered(&[(50.0, 100.0), (0.0, 50.0), (100.0, 50.0), (50.0, 0.0)]);
+    assert_eq!(
+        diamond,
+        vec![(50.0, 0.0), (100.0, 50.0), (50.0, 100.0), (0.0, 50.0)]
+    );
+}
@@ -0,0 +1,223 @@
+use super::{
+    language::ServerLanguage,
+    ocr::{OcrBackend, OcrCandidate, PageSegMode},
+};
+use opencv::core::Mat;
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+/// Work handed to a worker, run with the backend owned by that worker.
+type Job = Box<dyn FnOnce(&mut dyn OcrBackend) + Send>;
+
+/// A pool of worker threads that each own an OCR backend.
+///
+/// Tesseract keeps its state in the `TessApi` instance and reads one crop at a time, so a
+/// single instance keeps batch scans on one core. The pool runs one backend per thread and
+/// distributes work across them: single crops and the tag crops of one image through the
+/// `OcrBackend` implementation, whole images through `OcrPool::map`. Results always come back
+/// in submission order, regardless of which worker finished first.
+///
+/// # Example Usage
+/// ```rust
+/// let config: Config = Config::default();
+/// let mut pool: OcrPool = OcrPool::new(4, move || {
+///     Ok(Box::new(TesseractBackend::new("/usr/share/tessdata", ServerLanguage::English)?))
+/// })?;
+/// let tags: Vec<Tag> = image_to_tags(&panel, &mut pool, &config)?;
+/// ```
+pub struct OcrPool {
+    jobs: Option<mpsc::Sender<Job>>,
+    workers: Vec<thread::JoinHandle<()>>,
+    whitelist: String,
+    page_seg_mode: PageSegMode,
+    languages: Vec<ServerLanguage>,
+}
+
+impl OcrPool {
+    /// Starts `threads` workers, each with a backend created by `factory`.
+    ///
+    /// The factory runs on the worker thread itself, so the backend never has to be moved
+    /// between threads. A thread count of 0 is treated as 1.
+    ///
+    /// # Errors
+    /// Returns an error if a thread cannot be spawned or the factory fails on any worker.
+    pub fn new<F>(threads: usize, factory: F) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        F: Fn() -> Result<Box<dyn OcrBackend>, Box<dyn std::error::Error>> + Send + Sync + 'static,
+    {
+        let threads: usize = threads.max(1);
+        let factory: Arc<F> = Arc::new(factory);
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver: Arc<Mutex<mpsc::Receiver<Job>>> = Arc::new(Mutex::new(receiver));
+        let (ready, ready_receiver) = mpsc::channel::<Result<(), String>>();
+
+        let mut pool: OcrPool = OcrPool {
+            jobs: Some(jobs),
+            workers: vec![],
+            whitelist: String::new(),
+            page_seg_mode: PageSegMode::default(),
+            languages: vec![],
+        };
+        for index in 0..threads {
+            let factory: Arc<F> = Arc::clone(&factory);
+            let receiver: Arc<Mutex<mpsc::Receiver<Job>>> = Arc::clone(&receiver);
+            let ready: mpsc::Sender<Result<(), String>> = ready.clone();
+            let worker: thread::JoinHandle<()> = thread::Builder::new()
+                .name(format!("ocr-{}", index))
+                .spawn(move || {
+                    let mut backend: Box<dyn OcrBackend> = match factory() {
+                        Ok(backend) => backend,
+                        Err(e) => {
+                            _ = ready.send(Err(e.to_string()));
+                            return;
+                        }
+                    };
+                    _ = ready.send(Ok(()));
+                    loop {
+                        let job: Result<Job, mpsc::RecvError> = receiver.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => job(backend.as_mut()),
+                            Err(_) => break,
+                        }
+                    }
+                })?;
+            pool.workers.push(worker);
+        }
+        drop(ready);
+        for started in ready_receiver.iter().take(threads) {
+            started?;
+        }
+        Ok(pool)
+    }
+
+    /// Applies `f` to every input on the workers and returns the results in input order.
+    ///
+    /// # Parameters
+    /// - `inputs`: The work items, e.g. image paths or crops.
+    /// - `f`: Called on a worker with its backend and one input.
+    ///
+    /// # Errors
+    /// Returns an error if a worker has stopped, e.g. because `f` panicked.
+    pub fn map<I, T, F>(&self, inputs: Vec<I>, f: F) -> Result<Vec<T>, Box<dyn std::error::Error>>
+    where
+        I: Send + 'static,
+        T: Send + 'static,
+        F: Fn(&mut dyn OcrBackend, I) -> T + Send + Sync + 'static,
+    {
+        let jobs: &mpsc::Sender<Job> = self.jobs.as_ref().ok_or("the OCR pool is shut down")?;
+        let f: Arc<F> = Arc::new(f);
+        let count: usize = inputs.len();
+        let (results, result_receiver) = mpsc::channel::<(usize, T)>();
+        for (index, input) in inputs.into_iter().enumerate() {
+            let f: Arc<F> = Arc::clone(&f);
+            let results: mpsc::Sender<(usize, T)> = results.clone();
+            jobs.send(Box::new(move |backend: &mut dyn OcrBackend| {
+                _ = results.send((index, f(backend, input)));
+            }))
+            .map_err(|_| "all OCR workers have stopped")?;
+        }
+        drop(results);
+
+        let mut collected: Vec<(usize, T)> = result_receiver.iter().collect();
+        if collected.len() != count {
+            return Err("an OCR worker stopped before finishing its work".into());
+        }
+        collected.sort_by_key(|(index, _): &(usize, T)| *index);
+        Ok(collected.into_iter().map(|(_, result)| result).collect())
+    }
+}
+
+impl Drop for OcrPool {
+    /// Closes the job queue and waits for the workers to finish their current job.
+    fn drop(&mut self) {
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            _ = worker.join();
+        }
+    }
+}
+
+impl OcrBackend for OcrPool {
+    /// Remembers the whitelist. Every job sets it on its worker's backend before recognizing.
+    fn set_whitelist(&mut self, chars: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.whitelist = chars.into();
+        Ok(())
+    }
+
+    /// Remembers the mode. Every job sets it on its worker's backend before recognizing.
+    fn set_page_seg_mode(&mut self, mode: PageSegMode) -> Result<(), Box<dyn std::error::Error>> {
+        self.page_seg_mode = mode;
+        Ok(())
+    }
+
+    /// Remembers the language. Every job switches its worker's backend to it before setting
+    /// the whitelist and the mode.
+    fn set_language(&mut self, language: ServerLanguage) -> Result<(), Box<dyn std::error::Error>> {
+        self.languages = vec![language];
+        Ok(())
+    }
+
+    /// Remembers the languages. Every job switches its worker's backend to them before
+    /// setting the whitelist and the mode.
+    fn set_languages(
+        &mut self,
+        languages: &[ServerLanguage],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.languages = languages.to_vec();
+        Ok(())
+    }
+
+    fn recognize(
+        &mut self,
+        grayscale: &Mat,
+    ) -> Result<Vec<OcrCandidate>, Box<dyn std::error::Error>> {
+        let mut readings: Vec<Vec<OcrCandidate>> =
+            self.recognize_batch(std::slice::from_ref(grayscale))?;
+        Ok(readings.remove(0))
+    }
+
+    /// Recognizes the crops concurrently, one crop per job.
+    fn recognize_batch(
+        &mut self,
+        crops: &[Mat],
+    ) -> Result<Vec<Vec<OcrCandidate>>, Box<dyn std::error::Error>> {
+        let whitelist: String = self.whitelist.clone();
+        let page_seg_mode: PageSegMode = self.page_seg_mode;
+        let languages: Vec<ServerLanguage> = self.languages.clone();
+        let readings: Vec<Result<Vec<OcrCandidate>, String>> = self.map(
+            crops.to_vec(),
+            move |backend: &mut dyn OcrBackend, crop: Mat| {
+                let switched: Result<(), Box<dyn std::error::Error>> = match languages.as_slice() {
+                    [] => Ok(()),
+                    [language] => backend.set_language(*language),
+                    languages => backend.set_languages(languages),
+                };
+                switched
+                    .and_then(|_| backend.set_whitelist(&whitelist))
+                    .and_then(|_| backend.set_page_seg_mode(page_seg_mode))
+                    .and_then(|_| backend.recognize(&crop))
+                    .map_err(|e: Box<dyn std::error::Error>| e.to_string())
+            },
+        )?;
+        readings
+            .into_iter()
+            .map(|reading: Result<Vec<OcrCandidate>, String>| reading.map_err(Into::into))
+            .collect()
+    }
+}
+
+#[test]
+fn map_keeps_input_order() -> Result<(), Box<dyn std::error::Error>> {
+    let pool: OcrPool = OcrPool::new(4, || {
+        Ok(Box::new(super::ocr::MockBackend::new(&[])) as Box<dyn OcrBackend>)
+    })?;
+    let inputs: Vec<u64> = (0..32).collect();
+    let doubled: Vec<u64> = pool.map(inputs, |_: &mut dyn OcrBackend, n: u64| {
+        thread::sleep(std::time::Duration::from_millis(32 - n));
+        n * 2
+    })?;
+    assert_eq!(doubled, (0..32).map(|n: u64| n * 2).collect::<Vec<u64>>());
+    Ok(())
+}
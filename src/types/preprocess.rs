@@ -0,0 +1,459 @@
+use super::errors::ConfigError;
+use opencv::{
+    core::{self, Mat, Size},
+    imgcodecs, imgproc, photo,
+    prelude::*,
+};
+use std::{
+    fmt,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Counts pipeline runs so that debug dumps of different images do not overwrite each other.
+static DEBUG_RUN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A single image transformation in a preprocessing `Pipeline`.
+///
+/// Stages are stateless and shared between threads, so they only hold their parameters.
+pub trait PreprocessStage: Send + Sync {
+    /// Returns the stage in the same notation it is configured with, e.g. `threshold(160)`.
+    fn name(&self) -> String;
+
+    /// Applies the stage to `image` and returns the transformed image.
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn std::error::Error>>;
+}
+
+/// Converts a BGR image to grayscale. Single channel images are passed through.
+pub struct Grayscale;
+
+impl PreprocessStage for Grayscale {
+    fn name(&self) -> String {
+        "grayscale".into()
+    }
+
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+        if image.channels() == 1 {
+            return Ok(image.try_clone()?);
+        }
+        let mut gray: Mat = Mat::default();
+        imgproc::cvt_color(
+            image,
+            &mut gray,
+            imgproc::COLOR_BGR2GRAY,
+            0,
+            core::AlgorithmHint::ALGO_HINT_ACCURATE,
+        )?;
+        Ok(gray)
+    }
+}
+
+/// Contrast limited adaptive histogram equalization on a grayscale image.
+pub struct Clahe {
+    pub clip_limit: f64,
+    pub tiles: i32,
+}
+
+impl PreprocessStage for Clahe {
+    fn name(&self) -> String {
+        format!("clahe({}, {})", self.clip_limit, self.tiles)
+    }
+
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+        let mut clahe: core::Ptr<imgproc::CLAHE> =
+            imgproc::create_clahe(self.clip_limit, Size::new(self.tiles, self.tiles))?;
+        let mut equalized: Mat = Mat::default();
+        clahe.apply(image, &mut equalized)?;
+        Ok(equalized)
+    }
+}
+
+/// Non-local means denoising on a grayscale image.
+pub struct Denoise {
+    pub strength: f32,
+}
+
+impl PreprocessStage for Denoise {
+    fn name(&self) -> String {
+        format!("denoise({})", self.strength)
+    }
+
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+        let mut denoised: Mat = Mat::default();
+        photo::fast_nl_means_denoising(image, &mut denoised, self.strength, 7, 21)?;
+        Ok(denoised)
+    }
+}
+
+/// How a `Threshold` stage picks its threshold.
+pub enum ThresholdMode {
+    /// A fixed global threshold.
+    Fixed(f64),
+    /// A global threshold chosen by Otsu's method.
+    Otsu,
+    /// A per-pixel threshold from the Gaussian weighted neighbourhood of `block_size` pixels,
+    /// minus `c`.
+    Adaptive { block_size: i32, c: f64 },
+}
+
+/// Binarizes a grayscale image.
+pub struct Threshold(pub ThresholdMode);
+
+impl PreprocessStage for Threshold {
+    fn name(&self) -> String {
+        match self.0 {
+            ThresholdMode::Fixed(value) => format!("threshold({})", value),
+            ThresholdMode::Otsu => "threshold(otsu)".into(),
+            ThresholdMode::Adaptive { block_size, c } => {
+                format!("threshold(adaptive, {}, {})", block_size, c)
+            }
+        }
+    }
+
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+        let mut threshed: Mat = Mat::default();
+        match self.0 {
+            ThresholdMode::Fixed(value) => {
+                imgproc::threshold(image, &mut threshed, value, 255.0, imgproc::THRESH_BINARY)?;
+            }
+            ThresholdMode::Otsu => {
+                imgproc::threshold(
+                    image,
+                    &mut threshed,
+                    0.0,
+                    255.0,
+                    imgproc::THRESH_BINARY | imgproc::THRESH_OTSU,
+                )?;
+            }
+            ThresholdMode::Adaptive { block_size, c } => {
+                imgproc::adaptive_threshold(
+                    image,
+                    &mut threshed,
+                    255.0,
+                    imgproc::ADAPTIVE_THRESH_GAUSSIAN_C,
+                    imgproc::THRESH_BINARY,
+                    block_size,
+                    c,
+                )?;
+            }
+        }
+        Ok(threshed)
+    }
+}
+
+/// A morphological opening (removes specks) or closing (fills gaps) with a square kernel.
+pub struct Morphology {
+    pub operation: i32,
+    pub size: i32,
+}
+
+impl PreprocessStage for Morphology {
+    fn name(&self) -> String {
+        match self.operation {
+            imgproc::MORPH_OPEN => format!("open({})", self.size),
+            _ => format!("close({})", self.size),
+        }
+    }
+
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+        let kernel: Mat = imgproc::get_structuring_element_def(
+            imgproc::MORPH_RECT,
+            Size::new(self.size, self.size),
+        )?;
+        let mut morphed: Mat = Mat::default();
+        imgproc::morphology_ex_def(image, &mut morphed, self.operation, &kernel)?;
+        Ok(morphed)
+    }
+}
+
+/// Unsharp masking: adds `amount` times the difference between the image and its blur.
+pub struct Sharpen {
+    pub amount: f64,
+}
+
+impl PreprocessStage for Sharpen {
+    fn name(&self) -> String {
+        format!("sharpen({})", self.amount)
+    }
+
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+        let mut blurred: Mat = Mat::default();
+        imgproc::gaussian_blur_def(image, &mut blurred, Size::new(0, 0), 1.0)?;
+        let mut sharpened: Mat = Mat::default();
+        core::add_weighted_def(
+            image,
+            1.0 + self.amount,
+            &blurred,
+            -self.amount,
+            0.0,
+            &mut sharpened,
+        )?;
+        Ok(sharpened)
+    }
+}
+
+/// Scales the image up by `factor` with bicubic interpolation.
+pub struct Upscale {
+    pub factor: f64,
+}
+
+impl PreprocessStage for Upscale {
+    fn name(&self) -> String {
+        format!("upscale({})", self.factor)
+    }
+
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+        let mut scaled: Mat = Mat::default();
+        imgproc::resize(
+            image,
+            &mut scaled,
+            Size::default(),
+            self.factor,
+            self.factor,
+            imgproc::INTER_CUBIC,
+        )?;
+        Ok(scaled)
+    }
+}
+
+/// An ordered chain of `PreprocessStage`s.
+///
+/// Pipelines are configured as a comma separated list of stages, each optionally followed by
+/// its arguments in parentheses:
+///
+/// | Stage | Arguments | Default |
+/// |-------|-----------|---------|
+/// | `grayscale` | | |
+/// | `clahe` | clip limit, tile count | `clahe(2, 8)` |
+/// | `denoise` | filter strength | `denoise(10)` |
+/// | `threshold` | value, `otsu` or `adaptive, block size, c` | `threshold(otsu)` |
+/// | `open`, `close` | kernel size | `open(3)` |
+/// | `sharpen` | amount | `sharpen(1)` |
+/// | `upscale` | factor | `upscale(2)` |
+///
+/// # Example
+/// ```text
+/// preprocess_tag = clahe(2.0, 8), upscale(2), threshold(otsu), open(2)
+/// ```
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    name: String,
+    stages: Vec<Arc<dyn PreprocessStage>>,
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stages: Vec<String> = self
+            .stages
+            .iter()
+            .map(|stage: &Arc<dyn PreprocessStage>| stage.name())
+            .collect();
+        write!(f, "{}: {}", self.name, stages.join(", "))
+    }
+}
+
+impl Pipeline {
+    /// Creates a pipeline from a list of stages.
+    ///
+    /// `name` identifies the pipeline in debug dumps.
+    pub fn new(name: &str, stages: Vec<Arc<dyn PreprocessStage>>) -> Self {
+        Pipeline {
+            name: name.into(),
+            stages,
+        }
+    }
+
+    /// Parses a pipeline from its configured notation.
+    ///
+    /// # Parameters
+    /// - `key`: The config key the pipeline is read from. It names the pipeline and is
+    ///   reported in errors.
+    /// - `spec`: The comma separated list of stages.
+    ///
+    /// # Errors
+    /// Returns `ConfigError::InvalidValue` if a stage is unknown or its arguments are invalid.
+    pub(crate) fn parse(key: &str, spec: &str) -> Result<Self, ConfigError> {
+        let invalid = || ConfigError::InvalidValue(key.into(), spec.into());
+        let mut stages: Vec<Arc<dyn PreprocessStage>> = vec![];
+        for stage in split_stages(spec).ok_or_else(invalid)? {
+            let (name, args) = match stage.split_once('(') {
+                Some((name, rest)) => {
+                    let args: &str = rest.strip_suffix(')').ok_or_else(invalid)?;
+                    (
+                        name.trim(),
+                        args.split(',').map(str::trim).collect::<Vec<&str>>(),
+                    )
+                }
+                None => (stage, vec![]),
+            };
+            let number = |index: usize, default: f64| -> Result<f64, ConfigError> {
+                match args.get(index) {
+                    Some(arg) => arg.parse::<f64>().map_err(|_| invalid()),
+                    None => Ok(default),
+                }
+            };
+            let parsed: Arc<dyn PreprocessStage> = match name {
+                "grayscale" => Arc::new(Grayscale),
+                "clahe" => Arc::new(Clahe {
+                    clip_limit: number(0, 2.0)?,
+                    tiles: number(1, 8.0)? as i32,
+                }),
+                "denoise" => Arc::new(Denoise {
+                    strength: number(0, 10.0)? as f32,
+                }),
+                "threshold" => Arc::new(Threshold(match args.first().copied() {
+                    None | Some("otsu") => ThresholdMode::Otsu,
+                    Some("adaptive") => ThresholdMode::Adaptive {
+                        block_size: number(1, 31.0)? as i32,
+                        c: number(2, 10.0)?,
+                    },
+                    Some(_) => ThresholdMode::Fixed(number(0, 0.0)?),
+                })),
+                "open" | "close" => Arc::new(Morphology {
+                    operation: if name == "open" {
+                        imgproc::MORPH_OPEN
+                    } else {
+                        imgproc::MORPH_CLOSE
+                    },
+                    size: number(0, 3.0)? as i32,
+                }),
+                "sharpen" => Arc::new(Sharpen {
+                    amount: number(0, 1.0)?,
+                }),
+                "upscale" => Arc::new(Upscale {
+                    factor: number(0, 2.0)?,
+                }),
+                _ => return Err(invalid()),
+            };
+            stages.push(parsed);
+        }
+        Ok(Pipeline::new(key, stages))
+    }
+
+    /// Runs all stages in order.
+    ///
+    /// # Parameters
+    /// - `image`: The input of the first stage.
+    /// - `debug_dir`: If set, the input and the output of every stage are written to this
+    ///   directory as `<pipeline>_<run>_<step>_<stage>.png`. Without it, the run counter is not
+    ///   advanced and the input is only copied if the pipeline has no stages.
+    ///
+    /// # Errors
+    /// Returns the first error raised by a stage, or an error if a debug image cannot be
+    /// written.
+    pub fn run(
+        &self,
+        image: &Mat,
+        debug_dir: Option<&Path>,
+    ) -> Result<Mat, Box<dyn std::error::Error>> {
+        let Some(dir) = debug_dir else {
+            let mut current: Option<Mat> = None;
+            for stage in self.stages.iter() {
+                current = Some(stage.apply(current.as_ref().unwrap_or(image))?);
+            }
+            return Ok(match current {
+                Some(current) => current,
+                None => image.try_clone()?,
+            });
+        };
+
+        let run: usize = DEBUG_RUN_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dump =
+            |step: usize, stage: &str, image: &Mat| -> Result<(), Box<dyn std::error::Error>> {
+                let file_name: String =
+                    format!("{}_{:06}_{:02}_{}.png", self.name, run, step, stage).replace(
+                        |c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '.',
+                        "_",
+                    );
+                imgcodecs::imwrite_def(&dir.join(file_name).to_string_lossy(), image)?;
+                Ok(())
+            };
+
+        dump(0, "input", image)?;
+        let mut current: Mat = image.try_clone()?;
+        for (index, stage) in self.stages.iter().enumerate() {
+            current = stage.apply(&current)?;
+            dump(index + 1, &stage.name(), &current)?;
+        }
+        Ok(current)
+    }
+}
+
+/// Splits a pipeline spec at the commas that are not inside parentheses.
+///
+/// Returns `None` if the parentheses are unbalanced.
+fn split_stages(spec: &str) -> Option<Vec<&str>> {
+    let mut stages: Vec<&str> = vec![];
+    let (mut depth, mut start) = (0i32, 0usize);
+    for (index, c) in spec.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                stages.push(spec[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+        if depth < 0 {
+            return None;
+        }
+    }
+    if depth != 0 {
+        return None;
+    }
+    stages.push(spec[start..].trim());
+    Some(
+        stages
+            .into_iter()
+            .filter(|s: &&str| !s.is_empty())
+            .collect(),
+    )
+}
+
+#[test]
+fn split_stages_respects_nested_parentheses_and_skips_empty_stages() {
+    assert_eq!(split_stages("a(b(c, d), e), f"), Some(vec!["a(b(c, d), e)", "f"]));
+    assert_eq!(
+        split_stages(" grayscale,, clahe(2, 8) ,"),
+        Some(vec!["grayscale", "clahe(2, 8)"])
+    );
+    assert_eq!(split_stages(""), Some(vec![]));
+    assert_eq!(split_stages("clahe(2, 8"), None);
+    assert_eq!(split_stages("clahe)2, 8("), None);
+}
+
+#[test]
+fn pipeline_parse_reads_stages_and_rejects_bad_arguments() {
+    let pipeline: Pipeline =
+        Pipeline::parse("preprocess.tags", "grayscale, threshold(adaptive, 21, 5), , upscale")
+            .unwrap();
+    assert_eq!(
+        format!("{:?}", pipeline),
+        "preprocess.tags: grayscale, threshold(adaptive, 21, 5), upscale(2)"
+    );
+    assert_eq!(
+        format!("{:?}", Pipeline::parse("preprocess.tags", " , ").unwrap()),
+        "preprocess.tags: "
+    );
+    for spec in [
+        "clahe(two)",
+        "upscale(2",
+        "sharpen(1))",
+        "denoise((10))",
+        "blur(3)",
+        "threshold(adaptive, x)",
+    ] {
+        assert!(
+            matches!(
+                Pipeline::parse("preprocess.tags", spec),
+                Err(ConfigError::InvalidValue(_, _))
+            ),
+            "{} was accepted",
+            spec
+        );
+    }
+}
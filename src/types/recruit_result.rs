@@ -0,0 +1,237 @@
+use super::{
+    ocr::{self, OcrBackend},
+    operators::OperatorDatabase,
+    template,
+};
+use opencv::{
+    core::{self, Mat, Point, Rect, Scalar},
+    imgproc,
+    prelude::*,
+};
+use std::fmt;
+
+/// Regions of the operator name and the rarity stars on the reveal screen as
+/// `(x, y, width, height)` fractions of the screen.
+static NAME_ROI: (f64, f64, f64, f64) = (0.04, 0.62, 0.46, 0.11);
+static STARS_ROI: (f64, f64, f64, f64) = (0.04, 0.54, 0.34, 0.08);
+static STAR_TEMPLATE: &str = "star.png";
+static STAR_ACCEPT_THRESH: f64 = 0.7;
+static MAX_RARITY: u8 = 6;
+static NAME_TEXT_THRESH: f64 = 170.0;
+static NAME_WHITELIST: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789- '";
+
+/// The operator revealed at the end of a recruitment.
+///
+/// # Fields
+/// - `raw_name`: The name as read by OCR.
+/// - `operator`: The matched operator name from the `OperatorDatabase`, if any.
+/// - `name_ratio`: The similarity between `raw_name` and `operator`.
+/// - `stars`: The number of rarity stars counted on screen, or `None` if the star template is
+///   not available.
+/// - `database_rarity`: The rarity stored for `operator` in the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecruitResult {
+    raw_name: String,
+    operator: Option<String>,
+    name_ratio: f64,
+    stars: Option<u8>,
+    database_rarity: Option<u8>,
+}
+
+impl RecruitResult {
+    /// Returns the name as read by OCR.
+    pub fn raw_name(&self) -> &str {
+        &self.raw_name
+    }
+
+    /// Returns the matched operator name, if the reading matched the database.
+    pub fn operator(&self) -> Option<&str> {
+        self.operator.as_deref()
+    }
+
+    /// Returns the similarity between the OCR reading and the matched operator name.
+    pub fn name_ratio(&self) -> f64 {
+        self.name_ratio
+    }
+
+    /// Returns the rarity of the operator.
+    ///
+    /// The star count on screen is preferred; the database rarity is used if the stars could
+    /// not be counted.
+    pub fn rarity(&self) -> Option<u8> {
+        self.stars.or(self.database_rarity)
+    }
+
+    /// Returns whether the counted stars disagree with the rarity in the database, which
+    /// indicates a misread name.
+    pub fn rarity_mismatch(&self) -> bool {
+        matches!((self.stars, self.database_rarity), (Some(s), Some(d)) if s != d)
+    }
+}
+
+impl fmt::Display for RecruitResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name: &str = self.operator().unwrap_or(&self.raw_name);
+        match self.rarity() {
+            Some(rarity) => write!(f, "{} ({}★)", name, rarity)?,
+            None => write!(f, "{}", name)?,
+        }
+        if self.operator.is_none() {
+            write!(f, " [not in operator database]")?;
+        } else if self.rarity_mismatch() {
+            write!(f, " [star count does not match database]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the revealed operator from a screenshot of the recruitment result screen.
+///
+/// # Parameters
+/// - `image`: A reference to the full screenshot in BGR color format (e.g., CV_8UC3).
+/// - `backend`: The OCR backend used for recognition. The character whitelist is
+///   overwritten.
+/// - `operators`: The database the name is matched against.
+///
+/// # Returns
+/// - `Result<RecruitResult, Box<dyn std::error::Error>>`: The name reading, its database
+///   match and the counted rarity.
+///
+/// # Processing Steps
+/// 1. **Name Extraction**: The name region is read with a whitelist of letters, digits and the
+///    punctuation used in operator names.
+///
+/// 2. **Name Matching**: The reading is matched against the `OperatorDatabase` by similarity
+///    ratio.
+///
+/// 3. **Star Counting**: The star template is matched across the star row. Every peak above
+///    `STAR_ACCEPT_THRESH` counts as one star; the area around a counted peak is suppressed so
+///    the same star is not counted twice.
+///
+/// # Example Usage
+/// ```rust
+/// let image: Mat = ...; // Load a screenshot of the recruitment result screen
+/// let mut backend: TesseractBackend = ...; // Initialize the OCR backend
+/// let result: RecruitResult = read_recruit_result(&image, &mut backend, &OperatorDatabase::default())?;
+/// println!("Recruited {}", result);
+/// ```
+///
+/// # Errors
+/// This function may return errors related to image processing operations or OCR processing.
+pub fn read_recruit_result(
+    image: &Mat,
+    backend: &mut dyn OcrBackend,
+    operators: &OperatorDatabase,
+) -> Result<RecruitResult, Box<dyn std::error::Error>> {
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &image,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+
+    backend.set_whitelist(NAME_WHITELIST)?;
+    let name_rect: Rect = ocr::relative_rect(gray.size()?, NAME_ROI);
+    let raw_name: String = ocr::read_region(backend, &gray, &name_rect, NAME_TEXT_THRESH, true)?;
+    let matched: Option<(&str, u8, f64)> = operators.best_match(&raw_name);
+
+    Ok(RecruitResult {
+        operator: matched.map(|(name, _, _)| name.to_string()),
+        name_ratio: matched.map_or(0.0, |(_, _, ratio)| ratio),
+        database_rarity: matched.map(|(_, rarity, _)| rarity),
+        stars: count_stars(&gray)?,
+        raw_name,
+    })
+}
+
+/// Counts the rarity stars in the star row of the reveal screen.
+///
+/// Returns `None` if the star template is missing from `TEMPLATE_DIR`. The template is
+/// captured from a result screenshot with `retag capture-template star.png ...`.
+fn count_stars(gray: &Mat) -> Result<Option<u8>, Box<dyn std::error::Error>> {
+    match template::load_template(STAR_TEMPLATE, gray.rows())? {
+        Some(star) => Ok(Some(count_star_matches(gray, &star)?)),
+        None => Ok(None),
+    }
+}
+
+/// Counts the matches of the star template in the star row. Every peak above
+/// `STAR_ACCEPT_THRESH` counts once; the area around a counted peak is suppressed.
+fn count_star_matches(gray: &Mat, star: &Mat) -> Result<u8, Box<dyn std::error::Error>> {
+    let row_rect: Rect = ocr::relative_rect(gray.size()?, STARS_ROI);
+    let row: Mat = gray.roi(row_rect)?.try_clone()?;
+    if star.cols() > row.cols() || star.rows() > row.rows() {
+        return Ok(0);
+    }
+    let mut scores: Mat = Mat::default();
+    imgproc::match_template_def(&row, star, &mut scores, imgproc::TM_CCOEFF_NORMED)?;
+
+    let mut stars: u8 = 0;
+    while stars < MAX_RARITY {
+        let mut max_val: f64 = 0.0;
+        let mut max_loc: Point = Point::default();
+        core::min_max_loc(
+            &scores,
+            None,
+            Some(&mut max_val),
+            None,
+            Some(&mut max_loc),
+            &core::no_array(),
+        )?;
+        if max_val < STAR_ACCEPT_THRESH {
+            break;
+        }
+        stars += 1;
+        let suppressed: Rect = Rect::new(
+            max_loc.x - star.cols() / 2,
+            max_loc.y - star.rows() / 2,
+            star.cols(),
+            star.rows(),
+        );
+        imgproc::rectangle(
+            &mut scores,
+            suppressed,
+            Scalar::all(-1.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+    }
+    Ok(stars)
+}
+
+#[test]
+fn count_star_matches_counts_each_star_once() -> Result<(), Box<dyn std::error::Error>> {
+    let mut gray: Mat =
+        Mat::new_rows_cols_with_default(360, 640, core::CV_8UC1, Scalar::all(30.0))?;
+    // Four synthetic stars: bright squares with a dark cross, 30 px apart in the star row.
+    for index in 0..4 {
+        let x: i32 = 40 + index * 30;
+        imgproc::rectangle(
+            &mut gray,
+            Rect::new(x, 200, 20, 20),
+            Scalar::all(220.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+        for rect in [Rect::new(x + 8, 200, 4, 20), Rect::new(x, 208, 20, 4)] {
+            imgproc::rectangle(
+                &mut gray,
+                rect,
+                Scalar::all(60.0),
+                imgproc::FILLED,
+                imgproc::LINE_8,
+                0,
+            )?;
+        }
+    }
+    let star: Mat = gray.roi(Rect::new(38, 198, 24, 24))?.try_clone()?;
+    assert_eq!(count_star_matches(&gray, &star)?, 4);
+
+    let blank: Mat = Mat::new_rows_cols_with_default(360, 640, core::CV_8UC1, Scalar::all(30.0))?;
+    assert_eq!(count_star_matches(&blank, &star)?, 0);
+    Ok(())
+}
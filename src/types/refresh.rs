@@ -0,0 +1,142 @@
+use super::ocr;
+use opencv::{
+    core::{self, Mat, Point, Rect, Vector},
+    imgproc,
+    prelude::*,
+};
+
+/// Region to the right of the tag panel that holds the refresh button, as
+/// `(x, y, width, height)` fractions of the screen.
+static REFRESH_BUTTON_ROI: (f64, f64, f64, f64) = (0.68, 0.52, 0.14, 0.20);
+/// Minimum area of the button outline relative to the search region.
+static MIN_BUTTON_SIZE: f64 = 0.05;
+/// Mean saturation in `[0, 1]` above which the button is considered active. The active button
+/// is drawn in the accent color, the disabled one in gray.
+static REFRESH_ACTIVE_SATURATION: f64 = 0.25;
+
+/// The refresh button of the tag selection screen.
+///
+/// # Fields
+/// - `bounding_box`: The location of the button in the screenshot.
+/// - `available`: Whether the button is active, i.e. a tag refresh is possible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshButton {
+    pub bounding_box: Rect,
+    pub available: bool,
+}
+
+/// Locates the refresh button next to the tag panel and determines whether it is active.
+///
+/// # Parameters
+/// - `image`: A reference to the full screenshot in BGR color format (e.g., CV_8UC3).
+///
+/// # Returns
+/// - `Result<Option<RefreshButton>, Box<dyn std::error::Error>>`: The button, or `None` if no
+///   button outline was found in `REFRESH_BUTTON_ROI`.
+///
+/// # Processing Steps
+/// 1. **Button Detection**: The search region is converted to grayscale, edges are detected
+///    and the largest four-cornered contour covering at least `MIN_BUTTON_SIZE` of the region
+///    is taken as the button.
+///
+/// 2. **State Detection**: The button is converted to HSV. The active button is colored, the
+///    disabled one is gray, so a mean saturation above `REFRESH_ACTIVE_SATURATION` marks the
+///    button as available.
+///
+/// # Example Usage
+/// ```rust
+/// let image: Mat = ...; // Load a screenshot of the tag selection screen
+/// if let Some(button) = detect_refresh_button(&image)? {
+///     println!("Refresh available: {}", button.available);
+/// }
+/// ```
+///
+/// # Errors
+/// This function may return errors related to image processing operations.
+pub fn detect_refresh_button(
+    image: &Mat,
+) -> Result<Option<RefreshButton>, Box<dyn std::error::Error>> {
+    let region: Rect = ocr::relative_rect(image.size()?, REFRESH_BUTTON_ROI);
+    let search: Mat = image.roi(region)?.try_clone()?;
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &search,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    let mut edges: Mat = Mat::default();
+    imgproc::canny_def(&gray, &mut edges, 50.0, 150.0)?;
+    let mut contours: Vector<Vector<Point>> = Vector::new();
+    imgproc::find_contours_def(
+        &edges,
+        &mut contours,
+        imgproc::RETR_EXTERNAL,
+        imgproc::CHAIN_APPROX_SIMPLE,
+    )?;
+
+    let min_area: i32 = (MIN_BUTTON_SIZE * region.area() as f64) as i32;
+    let mut button: Option<Rect> = None;
+    for contour in contours.iter() {
+        let perimeter: f64 = imgproc::arc_length(&contour, true)?;
+        let mut poly: Vector<Point> = Vector::new();
+        imgproc::approx_poly_dp(&contour, &mut poly, 0.05 * perimeter, true)?;
+        if poly.len() != 4 {
+            continue;
+        }
+        let bounding: Rect = imgproc::bounding_rect(&poly)?;
+        if bounding.area() >= min_area && button.is_none_or(|b: Rect| b.area() < bounding.area()) {
+            button = Some(bounding);
+        }
+    }
+    let Some(button) = button else {
+        return Ok(None);
+    };
+
+    let mut hsv: Mat = Mat::default();
+    imgproc::cvt_color(
+        &search.roi(button)?,
+        &mut hsv,
+        imgproc::COLOR_BGR2HSV,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    let saturation: f64 = core::mean_def(&hsv)?.get(1).unwrap() / 255.0;
+    Ok(Some(RefreshButton {
+        bounding_box: button + region.tl(),
+        available: saturation >= REFRESH_ACTIVE_SATURATION,
+    }))
+}
+
+#[test]
+fn detect_refresh_button_reads_state_and_translates_box() -> Result<(), Box<dyn std::error::Error>>
+{
+    let size: core::Size = core::Size::new(1000, 600);
+    let region: Rect = ocr::relative_rect(size, REFRESH_BUTTON_ROI);
+    let drawn: Rect = Rect::new(region.x + 20, region.y + 20, 100, 60);
+    let canvas = |color: Option<core::Scalar>| -> Result<Mat, Box<dyn std::error::Error>> {
+        let mut image: Mat =
+            Mat::new_size_with_default(size, core::CV_8UC3, core::Scalar::all(30.0))?;
+        if let Some(color) = color {
+            imgproc::rectangle(&mut image, drawn, color, imgproc::FILLED, imgproc::LINE_8, 0)?;
+        }
+        Ok(image)
+    };
+
+    let active: RefreshButton =
+        detect_refresh_button(&canvas(Some(core::Scalar::new(0.0, 140.0, 255.0, 0.0)))?)?
+            .unwrap();
+    assert!(active.available);
+    assert!((active.bounding_box.x - drawn.x).abs() <= 2);
+    assert!((active.bounding_box.y - drawn.y).abs() <= 2);
+    assert!((active.bounding_box.width - drawn.width).abs() <= 2);
+    assert!((active.bounding_box.height - drawn.height).abs() <= 2);
+
+    let disabled: RefreshButton =
+        detect_refresh_button(&canvas(Some(core::Scalar::all(128.0)))?)?.unwrap();
+    assert!(!disabled.available);
+
+    assert_eq!(detect_refresh_button(&canvas(None)?)?, None);
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use super::{
+    ocr::{self, OcrBackend},
+    template,
+};
+use opencv::{
+    core::{Mat, Rect},
+    imgproc,
+    prelude::MatTraitConst,
+};
+use std::{fmt, sync::Arc};
+
+/// Region of the top bar holding the resource counters as `(x, y, width, height)` fractions
+/// of the screen.
+static TOP_BAR_ROI: (f64, f64, f64, f64) = (0.50, 0.0, 0.50, 0.12);
+static RECRUITMENT_PERMIT_TEMPLATE: &str = "recruitment_permit.png";
+static EXPEDITED_PLAN_TEMPLATE: &str = "expedited_plan.png";
+static ICON_ACCEPT_THRESH: f64 = 0.75;
+/// Width of the counter next to an icon, relative to the icon width.
+static COUNTER_WIDTH_FACTOR: f64 = 2.5;
+static COUNTER_DIGIT_THRESH: f64 = 150.0;
+
+/// The resource counters shown in the top bar of the recruitment screens.
+///
+/// # Fields
+/// - `recruitment_permits`: The number of Recruitment Permits, if it could be read.
+/// - `expedited_plans`: The number of Expedited Plans, if it could be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceCounts {
+    recruitment_permits: Option<u32>,
+    expedited_plans: Option<u32>,
+}
+
+impl ResourceCounts {
+    /// Returns the number of Recruitment Permits, if it could be read.
+    pub fn recruitment_permits(&self) -> Option<u32> {
+        self.recruitment_permits
+    }
+
+    /// Returns the number of Expedited Plans, if it could be read.
+    pub fn expedited_plans(&self) -> Option<u32> {
+        self.expedited_plans
+    }
+}
+
+impl fmt::Display for ResourceCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |count: Option<u32>| count.map_or("unknown".to_string(), |c| c.to_string());
+        write!(
+            f,
+            "Recruitment Permits: {} / Expedited Plans: {}",
+            show(self.recruitment_permits),
+            show(self.expedited_plans)
+        )
+    }
+}
+
+/// Reads the Recruitment Permit and Expedited Plan counters from a screenshot.
+///
+/// # Parameters
+/// - `image`: A reference to the full screenshot in BGR color format (e.g., CV_8UC3).
+/// - `backend`: The OCR backend used for recognition. The character whitelist is
+///   overwritten with digits only.
+///
+/// # Returns
+/// - `Result<ResourceCounts, Box<dyn std::error::Error>>`: The counters that could be read.
+///   Counters whose icon is not found, or whose template is missing from `TEMPLATE_DIR`, are
+///   left as `None`. The templates are captured with `retag capture-template`.
+///
+/// # Processing Steps
+/// 1. **Color Conversion**: The screenshot is converted to grayscale and cropped to the top bar.
+///
+/// 2. **Icon Search**: Each resource icon is located with `template::best_match`. Matches below
+///    `ICON_ACCEPT_THRESH` are discarded.
+///
+/// 3. **Counter Extraction**: The number to the right of the icon is read with a digits-only
+///    whitelist.
+///
+/// # Example Usage
+/// ```rust
+/// let image: Mat = ...; // Load a screenshot of a recruitment screen
+/// let mut backend: TesseractBackend = ...; // Initialize the OCR backend
+/// let counts: ResourceCounts = read_resource_counts(&image, &mut backend)?;
+/// println!("{}", counts);
+/// ```
+///
+/// # Errors
+/// This function may return errors related to image processing operations or OCR processing.
+pub fn read_resource_counts(
+    image: &Mat,
+    backend: &mut dyn OcrBackend,
+) -> Result<ResourceCounts, Box<dyn std::error::Error>> {
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &image,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        opencv::core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    let bar_rect: Rect = ocr::relative_rect(gray.size()?, TOP_BAR_ROI);
+    let bar: Mat = gray.roi(bar_rect)?.try_clone()?;
+    backend.set_whitelist(ocr::DIGIT_WHITELIST)?;
+
+    let mut counts: [Option<u32>; 2] = [None; 2];
+    for (count, template_name) in counts
+        .iter_mut()
+        .zip([RECRUITMENT_PERMIT_TEMPLATE, EXPEDITED_PLAN_TEMPLATE])
+    {
+        if let Some(icon) = template::load_template(template_name, gray.rows())? {
+            *count = read_counter(backend, &bar, &icon)?;
+        }
+    }
+    let [recruitment_permits, expedited_plans] = counts;
+    Ok(ResourceCounts {
+        recruitment_permits,
+        expedited_plans,
+    })
+}
+
+/// Locates a resource icon in the top bar and reads the counter to its right.
+fn read_counter(
+    backend: &mut dyn OcrBackend,
+    bar: &Mat,
+    icon: &Arc<Mat>,
+) -> Result<Option<u32>, Box<dyn std::error::Error>> {
+    let Some((icon_rect, score)) = template::best_match(bar, icon)? else {
+        return Ok(None);
+    };
+    if score < ICON_ACCEPT_THRESH {
+        return Ok(None);
+    }
+    let x: i32 = icon_rect.x + icon_rect.width;
+    let width: i32 = ((icon_rect.width as f64 * COUNTER_WIDTH_FACTOR) as i32).min(bar.cols() - x);
+    if width <= 0 {
+        return Ok(None);
+    }
+    let counter_rect: Rect = Rect::new(x, icon_rect.y, width, icon_rect.height);
+    Ok(
+        ocr::read_region(backend, bar, &counter_rect, COUNTER_DIGIT_THRESH, true)?
+            .parse::<u32>()
+            .ok(),
+    )
+}
+
+#[test]
+fn read_counter_reads_digits_right_of_icon() -> Result<(), Box<dyn std::error::Error>> {
+    let mut bar: Mat = Mat::new_rows_cols_with_default(
+        60,
+        400,
+        opencv::core::CV_8UC1,
+        opencv::core::Scalar::all(30.0),
+    )?;
+    // A synthetic icon: a bright square with a dark cross, placed at x = 120.
+    imgproc::rectangle(
+        &mut bar,
+        Rect::new(120, 10, 40, 40),
+        opencv::core::Scalar::all(220.0),
+        imgproc::FILLED,
+        imgproc::LINE_8,
+        0,
+    )?;
+    for rect in [Rect::new(136, 10, 8, 40), Rect::new(120, 26, 40, 8)] {
+        imgproc::rectangle(
+            &mut bar,
+            rect,
+            opencv::core::Scalar::all(60.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+    }
+    let icon: Arc<Mat> = Arc::new(bar.roi(Rect::new(116, 6, 48, 48))?.try_clone()?);
+
+    let mut backend: super::ocr::MockBackend = super::ocr::MockBackend::new(&["42"]);
+    assert_eq!(read_counter(&mut backend, &bar, &icon)?, Some(42));
+    let mut backend: super::ocr::MockBackend = super::ocr::MockBackend::new(&["4O"]);
+    assert_eq!(read_counter(&mut backend, &bar, &icon)?, None);
+    Ok(())
+}
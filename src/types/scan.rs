@@ -0,0 +1,441 @@
+use super::{
+    config::Config,
+    language::ServerLanguage,
+    ocr::OcrBackend,
+    overview::{self, RecruitmentSlot},
+    perspective,
+    recruit_result::{self, RecruitResult},
+    refresh,
+    resources::{self, ResourceCounts},
+    screen::{self, ScreenClassification, ScreenKind},
+    tag::{self, Tag, TagType},
+    timer::{self, RecruitTime},
+};
+use opencv::core::Mat;
+use std::fmt;
+
+/// Recruitment time limits required by individual tags, as `(tag, minimum, maximum)`.
+///
+/// Top Operator only guarantees a 6★ operator at 9:00. Robot only yields the 1★ robot
+/// operators at 3:50 or less.
+static TIME_RULES: [(TagType, RecruitTime, RecruitTime); 2] = [
+    (TagType::TopOperator, RecruitTime::MAX, RecruitTime::MAX),
+    (
+        TagType::Robot,
+        RecruitTime::MIN,
+        RecruitTime {
+            hours: 3,
+            minutes: 50,
+        },
+    ),
+];
+
+/// Recruitment Permit count below which `ScanWarning::LowPermits` is raised.
+static LOW_PERMIT_THRESH: u32 = 5;
+
+/// A problem with the scanned recruitment that the player should be told about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanWarning {
+    /// A selected tag requires a different recruitment time than the one configured.
+    TimeMismatch {
+        tag: TagType,
+        configured: RecruitTime,
+        required_min: RecruitTime,
+        required_max: RecruitTime,
+    },
+    /// Fewer than `LOW_PERMIT_THRESH` Recruitment Permits are left.
+    LowPermits(u32),
+    /// A tag box could almost as well have been assigned a different tag.
+    AmbiguousTag { tag: TagType, alternative: TagType },
+}
+
+impl ScanWarning {
+    /// Writes the warning with the tags named in `language`.
+    fn write_localized(&self, f: &mut fmt::Formatter<'_>, language: ServerLanguage) -> fmt::Result {
+        match self {
+            Self::TimeMismatch {
+                tag,
+                configured,
+                required_min,
+                required_max,
+            } if required_min == required_max => write!(
+                f,
+                "{} needs a recruitment time of {}, but {} is set",
+                tag.display_name(language),
+                required_min,
+                configured
+            ),
+            Self::TimeMismatch {
+                tag,
+                configured,
+                required_min,
+                required_max,
+            } => write!(
+                f,
+                "{} needs a recruitment time between {} and {}, but {} is set",
+                tag.display_name(language),
+                required_min,
+                required_max,
+                configured
+            ),
+            Self::LowPermits(permits) => write!(f, "Only {} Recruitment Permits left", permits),
+            Self::AmbiguousTag { tag, alternative } => write!(
+                f,
+                "{} could also be {}",
+                tag.display_name(language),
+                alternative.display_name(language)
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ScanWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_localized(f, ServerLanguage::English)
+    }
+}
+
+/// Everything read from a single screenshot.
+///
+/// Which fields are filled depends on the detected screen. Screens other than the tag
+/// selection screen leave the tag panel fields empty, so stray rectangles on unrelated
+/// screenshots are never read as tags.
+///
+/// # Fields
+/// - `screen`: The screen the screenshot was classified as.
+/// - `slots`: The recruitment slots, on the overview screen.
+/// - `recruit_result`: The revealed operator, on the recruitment result screen.
+/// - `tags`: The tags found in the tag panel.
+/// - `recruit_time`: The configured recruitment time, if it could be read.
+/// - `resources`: The resource counters shown in the top bar.
+/// - `refresh_available`: Whether the tag refresh button is active, or `None` if the button
+///   was not found.
+/// - `warnings`: Problems detected by checking the tags against the other values.
+/// - `detected_language`: The server language detected from the tag panel, or `None` if
+///   detection is disabled or found no tags.
+/// - `display_language`: The language tags are named in when the result is displayed.
+#[derive(Debug)]
+pub struct ScanResult {
+    screen: ScreenClassification,
+    slots: Vec<RecruitmentSlot>,
+    recruit_result: Option<RecruitResult>,
+    tags: Vec<Tag>,
+    recruit_time: Option<RecruitTime>,
+    resources: ResourceCounts,
+    refresh_available: Option<bool>,
+    warnings: Vec<ScanWarning>,
+    detected_language: Option<ServerLanguage>,
+    display_language: ServerLanguage,
+}
+
+impl ScanResult {
+    /// Creates an empty result for the given screen.
+    fn empty(screen: ScreenClassification, display_language: ServerLanguage) -> Self {
+        ScanResult {
+            screen,
+            slots: vec![],
+            recruit_result: None,
+            tags: vec![],
+            recruit_time: None,
+            resources: ResourceCounts::default(),
+            refresh_available: None,
+            warnings: vec![],
+            detected_language: None,
+            display_language,
+        }
+    }
+
+    /// Returns the screen the screenshot was classified as.
+    pub fn screen(&self) -> ScreenClassification {
+        self.screen
+    }
+
+    /// Returns the recruitment slots read from the overview screen.
+    pub fn slots(&self) -> &[RecruitmentSlot] {
+        &self.slots
+    }
+
+    /// Returns the operator read from the recruitment result screen.
+    pub fn recruit_result(&self) -> Option<&RecruitResult> {
+        self.recruit_result.as_ref()
+    }
+
+    /// Returns the tags found in the tag panel.
+    pub fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    /// Returns the configured recruitment time, if it could be read.
+    pub fn recruit_time(&self) -> Option<RecruitTime> {
+        self.recruit_time
+    }
+
+    /// Returns the resource counters shown in the top bar.
+    pub fn resources(&self) -> ResourceCounts {
+        self.resources
+    }
+
+    /// Returns whether the tag refresh button is active, or `None` if it was not found.
+    pub fn refresh_available(&self) -> Option<bool> {
+        self.refresh_available
+    }
+
+    /// Returns the warnings raised for this scan.
+    pub fn warnings(&self) -> &[ScanWarning] {
+        &self.warnings
+    }
+
+    /// Returns the server language detected from the tag panel, if detection ran.
+    pub fn detected_language(&self) -> Option<ServerLanguage> {
+        self.detected_language
+    }
+}
+
+impl fmt::Display for ScanResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Screen: {}", self.screen)?;
+        match self.screen.kind {
+            ScreenKind::TagSelection => {}
+            ScreenKind::Overview => {
+                for slot in &self.slots {
+                    writeln!(f, "Slot {}: {}", slot.index() + 1, slot.state())?;
+                }
+                return Ok(());
+            }
+            ScreenKind::Result => {
+                if let Some(result) = &self.recruit_result {
+                    writeln!(f, "Recruited: {}", result)?;
+                }
+                return Ok(());
+            }
+            ScreenKind::Other => return Ok(()),
+        }
+        let tags: Vec<String> = self
+            .tags
+            .iter()
+            .map(|tag: &Tag| {
+                if tag.selected() {
+                    format!("[{}]", tag.tag_type().display_name(self.display_language))
+                } else {
+                    tag.tag_type()
+                        .display_name(self.display_language)
+                        .to_string()
+                }
+            })
+            .collect();
+        if let Some(language) = self.detected_language {
+            writeln!(f, "Language: {} (detected)", language)?;
+        }
+        writeln!(f, "Tags: {}", tags.join(", "))?;
+        match self.recruit_time {
+            Some(time) => writeln!(f, "Time: {}", time)?,
+            None => writeln!(f, "Time: unknown")?,
+        }
+        writeln!(f, "{}", self.resources)?;
+        match self.refresh_available {
+            Some(true) => writeln!(f, "Refresh: available")?,
+            Some(false) => writeln!(f, "Refresh: unavailable")?,
+            None => writeln!(f, "Refresh: unknown")?,
+        }
+        for warning in &self.warnings {
+            write!(f, "Warning: ")?;
+            warning.write_localized(f, self.display_language)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Classifies a screenshot and reads the values shown on the detected screen.
+///
+/// On the tag selection screen, the tags, the recruitment time, the resource counters and the
+/// state of the refresh button are read. On the overview screen, the slot states are read, and
+/// on the result screen the revealed operator. Other screens are only classified.
+///
+/// # Parameters
+/// - `image`: A reference to the full screenshot in BGR color format (e.g., CV_8UC3).
+/// - `backend`: The OCR backend used for recognition.
+/// - `config`: The active settings. If `photo_input` is set, the image is treated as a camera
+///   photo and rectified with `perspective::rectify_photo` first. `min_screen_confidence` is
+///   passed to `screen::classify_screen`. The backend is switched to `server_language`
+///   before anything is read, so scans of different servers can share a backend. If
+///   `detect_languages` is set, the tags are read in the language detected with
+///   `tag::detect_language` instead. Inputs with several images detect it once with
+///   `scan_image_with_detection`.
+///
+/// # Returns
+/// - `Result<ScanResult, Box<dyn std::error::Error>>`: The recognized values together with
+///   any warnings derived from them.
+///
+/// # Example Usage
+/// ```rust
+/// let image: Mat = ...; // Load a screenshot of the tag selection screen
+/// let mut backend: TesseractBackend = ...; // Initialize the OCR backend
+/// let result: ScanResult = scan_image(&image, &mut backend, &Config::default())?;
+/// print!("{}", result);
+/// ```
+///
+/// # Errors
+/// This function may return errors related to image processing operations or OCR processing.
+pub fn scan_image(
+    image: &Mat,
+    backend: &mut dyn OcrBackend,
+    config: &Config,
+) -> Result<ScanResult, Box<dyn std::error::Error>> {
+    scan_image_with_detection(image, backend, config, &mut None)
+}
+
+/// Runs `scan_image` for one of several images of the same input, such as the frames of a
+/// video, detecting the server language only once.
+///
+/// If `detected` holds a language, the tags are read in it without running
+/// `tag::detect_language`. Otherwise a detected language is stored in it for the next image.
+///
+/// # Errors
+/// Returns the errors of `scan_image`.
+pub(crate) fn scan_image_with_detection(
+    image: &Mat,
+    backend: &mut dyn OcrBackend,
+    config: &Config,
+    detected: &mut Option<ServerLanguage>,
+) -> Result<ScanResult, Box<dyn std::error::Error>> {
+    backend.set_language(config.server_language)?;
+    let rectified: Mat;
+    let image: &Mat = if config.photo_input {
+        rectified = perspective::rectify_photo(image)?;
+        &rectified
+    } else {
+        image
+    };
+
+    let screen: ScreenClassification = screen::classify_screen(
+        image,
+        config.min_screen_confidence,
+        config.widest_tag_box_size(&config.detect_languages),
+    )?;
+    match screen.kind {
+        ScreenKind::TagSelection => {}
+        ScreenKind::Overview => {
+            return Ok(ScanResult {
+                slots: overview::image_to_slots(image, backend)?,
+                ..ScanResult::empty(screen, config.display_language)
+            });
+        }
+        ScreenKind::Result => {
+            return Ok(ScanResult {
+                recruit_result: Some(recruit_result::read_recruit_result(
+                    image,
+                    backend,
+                    &config.operators,
+                )?),
+                ..ScanResult::empty(screen, config.display_language)
+            });
+        }
+        ScreenKind::Other => return Ok(ScanResult::empty(screen, config.display_language)),
+    }
+
+    let recruit_time: Option<RecruitTime> = timer::read_recruit_time(image, backend)?;
+    let resources: ResourceCounts = resources::read_resource_counts(image, backend)?;
+    let refresh_available: Option<bool> =
+        refresh::detect_refresh_button(image)?.map(|button| button.available);
+
+    let panel: Mat = tag::crop_recruitment_roi(image)?;
+    let detected_language: Option<ServerLanguage> = match *detected {
+        Some(language) => Some(language),
+        None => tag::detect_language(&panel, backend, config)?.map(|(language, _)| language),
+    };
+    *detected = detected_language;
+    let detected_config: Config;
+    let config: &Config = match detected_language {
+        Some(language) if language != config.server_language => {
+            detected_config = Config {
+                server_language: language,
+                ..config.clone()
+            };
+            &detected_config
+        }
+        _ => config,
+    };
+    backend.set_language(config.server_language)?;
+    backend.set_whitelist(&config.server_language.tag_whitelist())?;
+    let tags: Vec<Tag> = tag::image_to_tags(&panel, backend, config)?;
+
+    let mut warnings: Vec<ScanWarning> = check_time_rules(&tags, recruit_time);
+    warnings.extend(tags.iter().filter_map(|tag: &Tag| {
+        tag.recognition()
+            .ambiguous_with()
+            .map(|alternative: TagType| ScanWarning::AmbiguousTag {
+                tag: tag.tag_type(),
+                alternative,
+            })
+    }));
+    if let Some(permits) = resources.recruitment_permits() {
+        if permits < LOW_PERMIT_THRESH {
+            warnings.push(ScanWarning::LowPermits(permits));
+        }
+    }
+    Ok(ScanResult {
+        screen,
+        slots: vec![],
+        recruit_result: None,
+        tags,
+        recruit_time,
+        resources,
+        refresh_available,
+        warnings,
+        detected_language,
+        display_language: config.display_language,
+    })
+}
+
+/// Checks the selected tags against `TIME_RULES`.
+///
+/// Nothing is reported if the recruitment time could not be read.
+fn check_time_rules(tags: &[Tag], recruit_time: Option<RecruitTime>) -> Vec<ScanWarning> {
+    let Some(configured) = recruit_time else {
+        return vec![];
+    };
+    tags.iter()
+        .filter(|tag: &&Tag| tag.selected())
+        .filter_map(|tag: &Tag| {
+            TIME_RULES
+                .iter()
+                .find(|(rule_tag, _, _)| *rule_tag == tag.tag_type())
+                .filter(|(_, min, max)| configured < *min || configured > *max)
+                .map(|(rule_tag, min, max)| ScanWarning::TimeMismatch {
+                    tag: *rule_tag,
+                    configured,
+                    required_min: *min,
+                    required_max: *max,
+                })
+        })
+        .collect()
+}
+
+#[test]
+fn check_time_rules_flags_selected_tags_only() {
+    let tags: Vec<Tag> = vec![
+        Tag::with_type(TagType::TopOperator, true),
+        Tag::with_type(TagType::Robot, false),
+        Tag::with_type(TagType::Guard, true),
+    ];
+    let short: RecruitTime = RecruitTime::new(3, 50).unwrap();
+    assert_eq!(
+        check_time_rules(&tags, Some(short)),
+        vec![ScanWarning::TimeMismatch {
+            tag: TagType::TopOperator,
+            configured: short,
+            required_min: RecruitTime::MAX,
+            required_max: RecruitTime::MAX,
+        }]
+    );
+    assert!(check_time_rules(&tags, Some(RecruitTime::MAX)).is_empty());
+    assert!(check_time_rules(&tags, None).is_empty());
+
+    let robot: Vec<Tag> = vec![Tag::with_type(TagType::Robot, true)];
+    assert!(check_time_rules(&robot, Some(RecruitTime::MIN)).is_empty());
+    assert_eq!(
+        check_time_rules(&robot, Some(RecruitTime::new(4, 0).unwrap())).len(),
+        1
+    );
+}
@@ -0,0 +1,276 @@
+use super::{ocr, tag, template};
+use opencv::{
+    core::{self, Mat, Rect},
+    imgproc,
+    prelude::MatTraitConst,
+};
+use std::{fmt, sync::Arc};
+
+/// Number of tag buttons on the tag selection screen.
+static TAG_BOX_COUNT: usize = 5;
+/// Allowed deviation of a tag box height from the median height.
+static TAG_BOX_HEIGHT_TOLERANCE: f64 = 0.2;
+
+/// The kinds of screens retag knows how to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScreenKind {
+    TagSelection,
+    Overview,
+    Result,
+    Other,
+}
+
+impl fmt::Display for ScreenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TagSelection => write!(f, "Recruitment tag selection"),
+            Self::Overview => write!(f, "Recruitment overview"),
+            Self::Result => write!(f, "Recruitment result"),
+            Self::Other => write!(f, "Other"),
+        }
+    }
+}
+
+/// Anchor templates identifying each screen, as `(screen, template, search region)`.
+/// The search region is given as `(x, y, width, height)` fractions of the screen.
+static SCREEN_ANCHORS: [(ScreenKind, &str, (f64, f64, f64, f64)); 3] = [
+    (
+        ScreenKind::TagSelection,
+        "screen_tag_selection.png",
+        (0.20, 0.35, 0.60, 0.15),
+    ),
+    (
+        ScreenKind::Overview,
+        "screen_overview.png",
+        (0.0, 0.0, 0.50, 0.20),
+    ),
+    (
+        ScreenKind::Result,
+        "screen_result.png",
+        (0.0, 0.60, 1.0, 0.40),
+    ),
+];
+
+/// The label assigned to a screenshot by `classify_screen`.
+///
+/// # Fields
+/// - `kind`: The detected screen.
+/// - `confidence`: How certain the classification is, in `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenClassification {
+    pub kind: ScreenKind,
+    pub confidence: f64,
+}
+
+impl fmt::Display for ScreenClassification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:.2})", self.kind, self.confidence)
+    }
+}
+
+/// Determines which recruitment screen a screenshot shows.
+///
+/// # Parameters
+/// - `image`: A reference to the full screenshot in BGR color format (e.g., CV_8UC3).
+/// - `min_confidence`: The score a screen needs to be accepted. Below it, the screenshot is
+///   labeled `ScreenKind::Other`.
+/// - `box_size`: The limits of the tag box area passed to `tag::detect_tag_boxes` for the
+///   tag panel check.
+///
+/// # Returns
+/// - `Result<ScreenClassification, Box<dyn std::error::Error>>`: The best scoring screen and
+///   its score. For `ScreenKind::Other`, the confidence is one minus the best score.
+///
+/// # Processing Steps
+/// 1. **Anchor Matching**: For each screen in `SCREEN_ANCHORS`, its anchor template is searched
+///    in the expected region with `template::best_match`. The correlation score is the screen's
+///    score. Screens whose template is missing from `TEMPLATE_DIR` score zero; the anchors are
+///    captured from a screenshot of each screen with `retag capture-template`.
+///
+/// 2. **Tag Panel Check**: The tag selection screen is additionally scored by the layout of
+///    the tag panel: exactly `TAG_BOX_COUNT` boxes of similar height are expected. Its final
+///    score is the mean of the anchor and layout scores, or the layout score alone if no anchor
+///    template is available.
+///
+/// 3. **Decision**: The highest score wins if it reaches `min_confidence`.
+///
+/// # Example Usage
+/// ```rust
+/// let image: Mat = ...; // Load any screenshot
+/// let screen: ScreenClassification = classify_screen(&image, 0.6, (0.005, 0.25))?;
+/// if screen.kind == ScreenKind::TagSelection {
+///     // run the tag pipeline
+/// }
+/// ```
+///
+/// # Errors
+/// This function may return errors related to image processing operations.
+pub fn classify_screen(
+    image: &Mat,
+    min_confidence: f64,
+    box_size: (f64, f64),
+) -> Result<ScreenClassification, Box<dyn std::error::Error>> {
+    let mut anchors: Vec<(ScreenKind, Option<Arc<Mat>>, (f64, f64, f64, f64))> = Vec::new();
+    for (kind, template_name, region) in SCREEN_ANCHORS.iter() {
+        anchors.push((*kind, template::load_template(template_name, image.rows())?, *region));
+    }
+    classify_with_anchors(image, min_confidence, box_size, &anchors)
+}
+
+/// Scores a screenshot against the given anchors, as `(screen, template, search region)`.
+/// A missing template scores zero, except for the tag selection screen, which falls back to
+/// its tag panel layout score. See `classify_screen` for the decision rules.
+fn classify_with_anchors(
+    image: &Mat,
+    min_confidence: f64,
+    box_size: (f64, f64),
+    anchors: &[(ScreenKind, Option<Arc<Mat>>, (f64, f64, f64, f64))],
+) -> Result<ScreenClassification, Box<dyn std::error::Error>> {
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &image,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+
+    let mut best: ScreenClassification = ScreenClassification {
+        kind: ScreenKind::Other,
+        confidence: 0.0,
+    };
+    for (kind, template, region) in anchors.iter() {
+        let anchor: Option<f64> = match template {
+            Some(template) => Some(anchor_score(&gray, template, *region)?),
+            None => None,
+        };
+        let score: f64 = match (kind, anchor) {
+            (ScreenKind::TagSelection, Some(anchor)) => {
+                (anchor + tag_panel_score(image, box_size)?) / 2.0
+            }
+            (ScreenKind::TagSelection, None) => tag_panel_score(image, box_size)?,
+            (_, anchor) => anchor.unwrap_or(0.0),
+        };
+        if score > best.confidence {
+            best = ScreenClassification {
+                kind: *kind,
+                confidence: score,
+            };
+        }
+    }
+
+    if best.confidence < min_confidence {
+        return Ok(ScreenClassification {
+            kind: ScreenKind::Other,
+            confidence: 1.0 - best.confidence,
+        });
+    }
+    Ok(best)
+}
+
+/// Searches an anchor template in its region and returns the correlation score clamped to
+/// `[0, 1]`.
+fn anchor_score(
+    gray: &Mat,
+    anchor: &Mat,
+    region: (f64, f64, f64, f64),
+) -> Result<f64, Box<dyn std::error::Error>> {
+    let rect: Rect = ocr::relative_rect(gray.size()?, region);
+    let haystack: Mat = gray.roi(rect)?.try_clone()?;
+    Ok(template::best_match(&haystack, anchor)?.map_or(0.0, |(_, score)| score.clamp(0.0, 1.0)))
+}
+
+/// Scores how closely the tag panel region matches the expected layout of five equally tall
+/// tag buttons.
+fn tag_panel_score(image: &Mat, box_size: (f64, f64)) -> Result<f64, Box<dyn std::error::Error>> {
+    let panel: Mat = tag::crop_recruitment_roi(image)?;
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &panel,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    let boxes: Vec<Rect> = tag::detect_tag_boxes(&gray, box_size)?;
+    if boxes.is_empty() {
+        return Ok(0.0);
+    }
+    let mut heights: Vec<i32> = boxes.iter().map(|b: &Rect| b.height).collect();
+    heights.sort();
+    let median: f64 = heights[heights.len() / 2] as f64;
+    let similar: usize = heights
+        .iter()
+        .filter(|h: &&i32| ((**h as f64) - median).abs() <= TAG_BOX_HEIGHT_TOLERANCE * median)
+        .count();
+    let deviation: f64 = (similar as f64 - TAG_BOX_COUNT as f64).abs() / TAG_BOX_COUNT as f64;
+    Ok((1.0 - deviation).max(0.0))
+}
+
+#[cfg(test)]
+fn screen_anchors(
+    overview: Option<Arc<Mat>>,
+) -> Vec<(ScreenKind, Option<Arc<Mat>>, (f64, f64, f64, f64))> {
+    SCREEN_ANCHORS
+        .iter()
+        .map(|(kind, _, region)| {
+            let template: Option<Arc<Mat>> = match kind {
+                ScreenKind::Overview => overview.clone(),
+                _ => None,
+            };
+            (*kind, template, *region)
+        })
+        .collect()
+}
+
+#[test]
+fn classify_screen_finds_anchor_in_its_region() -> Result<(), Box<dyn std::error::Error>> {
+    let mut image: Mat =
+        Mat::new_rows_cols_with_default(360, 640, core::CV_8UC3, core::Scalar::all(30.0))?;
+    // A synthetic anchor: a bright bar with a dark cross inside the overview anchor region.
+    imgproc::rectangle(
+        &mut image,
+        Rect::new(40, 20, 60, 30),
+        core::Scalar::all(220.0),
+        imgproc::FILLED,
+        imgproc::LINE_8,
+        0,
+    )?;
+    for rect in [Rect::new(66, 20, 8, 30), Rect::new(40, 31, 60, 8)] {
+        imgproc::rectangle(
+            &mut image,
+            rect,
+            core::Scalar::all(60.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+    }
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &image,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    let anchor: Arc<Mat> = Arc::new(gray.roi(Rect::new(36, 16, 68, 38))?.try_clone()?);
+
+    let screen: ScreenClassification =
+        classify_with_anchors(&image, 0.6, (0.005, 0.25), &screen_anchors(Some(anchor)))?;
+    assert_eq!(screen.kind, ScreenKind::Overview);
+    assert!(screen.confidence > 0.9, "confidence {}", screen.confidence);
+    Ok(())
+}
+
+#[test]
+fn classify_screen_without_anchors_or_tag_panel_is_other() -> Result<(), Box<dyn std::error::Error>>
+{
+    let image: Mat =
+        Mat::new_rows_cols_with_default(360, 640, core::CV_8UC3, core::Scalar::all(30.0))?;
+    let screen: ScreenClassification =
+        classify_with_anchors(&image, 0.6, (0.005, 0.25), &screen_anchors(None))?;
+    assert_eq!(screen.kind, ScreenKind::Other);
+    assert_eq!(screen.confidence, 1.0);
+    Ok(())
+}
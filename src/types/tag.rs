@@ -1,12 +1,19 @@
-use super::errors;
-use difflib::get_close_matches;
-use leptess::tesseract::TessApi;
+use super::{
+    assignment,
+    cache::{self, CacheEntry, TagCache},
+    config::Config,
+    errors,
+    language::ServerLanguage,
+    matcher::TagMatcher,
+    ocr::{OcrBackend, OcrCandidate, PageSegMode},
+    preprocess::{Grayscale, PreprocessStage, Threshold, ThresholdMode, Upscale},
+};
 use opencv::{
     core::{Mat, Point, Rect, Size, Vector},
-    imgcodecs,
     imgproc::{self, CHAIN_APPROX_SIMPLE},
     prelude::MatTraitConst,
 };
+use std::fmt;
 
 static RECRUITMENT_ROI_VERTICAL: (f64, f64) = (
     0.45, // ignore top 45%
@@ -16,10 +23,95 @@ static RECRUITMENT_ROI_HORIZONTAL: (f64, f64) = (
     0.3, // ignore left 30%
     0.3, // ignore right 30%
 );
-static MIN_TAG_BOX_SIZE: f64 = 0.005;
-static MAX_TAG_BOX_SIZE: f64 = 0.250;
 static SELECTED_ACCEPT_THRESH: f64 = 0.5;
-static TAGS_STRINGS: [&str; 28] = [
+/// Minimum similarity between an OCR reading and a tag string for the reading to be accepted.
+static TAG_MATCH_THRESH: f64 = 0.5;
+/// Total similarity by which the best tag assignment has to beat the best alternative for a
+/// box. Boxes with a closer alternative are reported as ambiguous.
+static AMBIGUITY_MARGIN: f64 = 0.05;
+/// Fraction of the tag box width and height cut off on every side by `tag_crop`.
+static TAG_CROP_INSET: f64 = 0.05;
+/// Name of the first reading of a tag crop, which uses the `preprocess_tag` pipeline.
+static DEFAULT_PASS: &str = "default";
+/// Share of the letters read by `detect_language` that have to belong to one script for the
+/// script to decide the language.
+static SCRIPT_SHARE: f64 = 0.8;
+/// Share of the letters that have to be kana for Han characters to be read as Japanese
+/// rather than as Chinese or Japanese.
+static KANA_SHARE: f64 = 0.2;
+
+/// An alternative way of cutting out and reading a tag crop.
+///
+/// # Fields
+/// - `name`: Identifies the pass in the `Recognition` of the tags it won.
+/// - `threshold`: The binary threshold applied to the crop, or `None` for Otsu's method.
+/// - `inset`: Fraction of the box width and height cut off on every side.
+/// - `scale`: Factor the crop is scaled by before thresholding.
+/// - `page_seg_mode`: The page segmentation mode the crop is read with.
+/// - `weight`: How much a vote of the pass counts relative to the default reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OcrPass {
+    pub name: &'static str,
+    pub threshold: Option<f64>,
+    pub inset: f64,
+    pub scale: f64,
+    pub page_seg_mode: PageSegMode,
+    pub weight: f64,
+}
+
+/// The passes a tag crop is read again with when its default reading matches only weakly.
+static RETRY_PASSES: [OcrPass; 6] = [
+    OcrPass {
+        name: "otsu",
+        threshold: None,
+        inset: TAG_CROP_INSET,
+        scale: 1.0,
+        page_seg_mode: PageSegMode::SingleLine,
+        weight: 1.0,
+    },
+    OcrPass {
+        name: "threshold-120",
+        threshold: Some(120.0),
+        inset: TAG_CROP_INSET,
+        scale: 1.0,
+        page_seg_mode: PageSegMode::SingleLine,
+        weight: 0.8,
+    },
+    OcrPass {
+        name: "threshold-200",
+        threshold: Some(200.0),
+        inset: TAG_CROP_INSET,
+        scale: 1.0,
+        page_seg_mode: PageSegMode::SingleLine,
+        weight: 0.8,
+    },
+    OcrPass {
+        name: "inset-10",
+        threshold: None,
+        inset: 0.10,
+        scale: 1.0,
+        page_seg_mode: PageSegMode::SingleLine,
+        weight: 0.9,
+    },
+    OcrPass {
+        name: "upscale-2",
+        threshold: None,
+        inset: TAG_CROP_INSET,
+        scale: 2.0,
+        page_seg_mode: PageSegMode::SingleWord,
+        weight: 1.0,
+    },
+    OcrPass {
+        name: "raw-line",
+        threshold: None,
+        inset: 0.02,
+        scale: 1.5,
+        page_seg_mode: PageSegMode::RawLine,
+        weight: 0.9,
+    },
+];
+
+pub(crate) static TAGS_STRINGS: [&str; 28] = [
     "Medic",
     "Caster",
     "Vanguard",
@@ -50,7 +142,8 @@ static TAGS_STRINGS: [&str; 28] = [
     "Top-Operator",
 ];
 
-#[derive(Debug)]
+/// The recruitment tags. The variants are declared in the order of `TAGS_STRINGS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TagType {
     Medic,
     Caster,
@@ -84,13 +177,13 @@ pub enum TagType {
 
 /// Converts a `TagType` to its corresponding string representation.
 ///
-/// This implementation of the `ToString` trait allows for converting instances of the
-/// `TagType` enum into their respective string representations. Each variant of the enum
-/// is mapped to a specific string that describes the tag type.
+/// This implementation of the `Display` trait allows for formatting instances of the
+/// `TagType` enum as their respective strings, and provides `to_string`. Each variant of the
+/// enum is mapped to a specific string that describes the tag type.
 ///
 /// # Returns
-/// - `String`: The string representation of the `TagType` instance. Each variant is
-///   converted to a corresponding string that describes the tag type.
+/// - `fmt::Result`: The result of writing the string of the `TagType` instance to the
+///   formatter.
 ///
 /// # Example Usage
 /// ```rust
@@ -100,43 +193,106 @@ pub enum TagType {
 /// ```
 ///
 /// # Notes
-/// This implementation is useful for displaying tag types in user interfaces, logging,
-/// or any situation where a string representation of the tag type is needed.
-impl ToString for TagType {
-    fn to_string(&self) -> String {
-        match self {
-            Self::Medic => "Medic".into(),
-            Self::Caster => "Caster".into(),
-            Self::Vanguard => "Vanguard".into(),
-            Self::Guard => "Guard".into(),
-            Self::Defender => "Defender".into(),
-            Self::Supporter => "Supporter".into(),
-            Self::Melee => "Melee".into(),
-            Self::Debuff => "Debuff".into(),
-            Self::FastRedeploy => "Fast-Redeploy".into(),
-            Self::Shift => "Shift".into(),
-            Self::Summon => "Summon".into(),
-            Self::Support => "Support".into(),
-            Self::Survival => "Survival".into(),
-            Self::Elemental => "Elemental".into(),
-            Self::Ranged => "Ranged".into(),
-            Self::DpRecovery => "Dp-Recovery".into(),
-            Self::Starter => "Starter".into(),
-            Self::Slow => "Slow".into(),
-            Self::AoE => "AoE".into(),
-            Self::Sniper => "Sniper".into(),
-            Self::CrowdControl => "Crowd-Control".into(),
-            Self::Healing => "Healing".into(),
-            Self::DPS => "DPS".into(),
-            Self::Nuker => "Nuker".into(),
-            Self::SeniorOperator => "Senior-Operator".into(),
-            Self::Specialist => "Specialist".into(),
-            Self::Robot => "Robot".into(),
-            Self::TopOperator => "Top-Operator".into(),
+/// The string is the English tag string, which also identifies the tag in cache files,
+/// label files and classifier libraries. Use `TagType::display_name` to show a tag in the
+/// language of the user.
+impl fmt::Display for TagType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Medic => "Medic",
+            Self::Caster => "Caster",
+            Self::Vanguard => "Vanguard",
+            Self::Guard => "Guard",
+            Self::Defender => "Defender",
+            Self::Supporter => "Supporter",
+            Self::Melee => "Melee",
+            Self::Debuff => "Debuff",
+            Self::FastRedeploy => "Fast-Redeploy",
+            Self::Shift => "Shift",
+            Self::Summon => "Summon",
+            Self::Support => "Support",
+            Self::Survival => "Survival",
+            Self::Elemental => "Elemental",
+            Self::Ranged => "Ranged",
+            Self::DpRecovery => "Dp-Recovery",
+            Self::Starter => "Starter",
+            Self::Slow => "Slow",
+            Self::AoE => "AoE",
+            Self::Sniper => "Sniper",
+            Self::CrowdControl => "Crowd-Control",
+            Self::Healing => "Healing",
+            Self::DPS => "DPS",
+            Self::Nuker => "Nuker",
+            Self::SeniorOperator => "Senior-Operator",
+            Self::Specialist => "Specialist",
+            Self::Robot => "Robot",
+            Self::TopOperator => "Top-Operator",
+        })
+    }
+}
+
+/// Parses a tag string as listed in `TAGS_STRINGS` into a `TagType`.
+///
+/// Multi-word tags are also accepted with a space or without a separator instead of the
+/// hyphen, e.g. `Top Operator` and `TopOperator`. Strings that are not English are tried as
+/// the tag strings of every other `ServerLanguage`, see `ServerLanguage::parse_tag`.
+///
+/// # Errors
+/// Returns `TagError::InvalidTagString` if the string names no tag.
+impl std::str::FromStr for TagType {
+    type Err = errors::TagError;
+
+    fn from_str(tag_string: &str) -> Result<Self, Self::Err> {
+        match tag_string {
+            "Medic" => Ok(TagType::Medic),
+            "Caster" => Ok(TagType::Caster),
+            "Vanguard" => Ok(TagType::Vanguard),
+            "Guard" => Ok(TagType::Guard),
+            "Defender" => Ok(TagType::Defender),
+            "Supporter" => Ok(TagType::Supporter),
+            "Melee" => Ok(TagType::Melee),
+            "Debuff" => Ok(TagType::Debuff),
+            "Fast-Redeploy" | "FastRedeploy" | "Fast Redeploy" => Ok(TagType::FastRedeploy),
+            "Shift" => Ok(TagType::Shift),
+            "Summon" => Ok(TagType::Summon),
+            "Support" => Ok(TagType::Support),
+            "Survival" => Ok(TagType::Survival),
+            "Elemental" => Ok(TagType::Elemental),
+            "Ranged" => Ok(TagType::Ranged),
+            "Dp-Recovery" | "DpRecovery" | "Dp Recovery" => Ok(TagType::DpRecovery),
+            "Starter" => Ok(TagType::Starter),
+            "Slow" => Ok(TagType::Slow),
+            "AoE" => Ok(TagType::AoE),
+            "Sniper" => Ok(TagType::Sniper),
+            "Crowd-Control" | "CrowdControl" | "Crowd Control" => Ok(TagType::CrowdControl),
+            "Healing" => Ok(TagType::Healing),
+            "DPS" => Ok(TagType::DPS),
+            "Nuker" => Ok(TagType::Nuker),
+            "SeniorOperator" | "Senior-Operator" | "Senior Operator" => Ok(TagType::SeniorOperator),
+            "Specialist" => Ok(TagType::Specialist),
+            "Robot" => Ok(TagType::Robot),
+            "Top-Operator" | "TopOperator" | "Top Operator" => Ok(TagType::TopOperator),
+            _ => ServerLanguage::ALL
+                .iter()
+                .filter(|language: &&ServerLanguage| **language != ServerLanguage::English)
+                .find_map(|language: &ServerLanguage| language.parse_tag(tag_string))
+                .ok_or(errors::TagError::InvalidTagString),
         }
     }
 }
 
+impl TagType {
+    /// Returns the tag string the client of `language` shows for the tag.
+    ///
+    /// # Example Usage
+    /// ```rust
+    /// assert_eq!(TagType::Medic.display_name(ServerLanguage::Chinese), "医疗干员");
+    /// ```
+    pub fn display_name(self, language: ServerLanguage) -> &'static str {
+        language.tag_strings()[self as usize]
+    }
+}
+
 /// Represents a tag detected in an image with associated properties.
 ///
 /// The `Tag` struct encapsulates information about a tag, including its type, selection status,
@@ -152,6 +308,7 @@ impl ToString for TagType {
 /// - `bounding_box`: A `Rect` object representing the bounding box of the tag in the image.
 ///   This field defines the rectangular area that encompasses the tag, which is useful for
 ///   visualization and further processing.
+/// - `recognition`: The evidence the tag was recognized from, see `Recognition`.
 ///
 /// # Example Usage
 /// ```rust
@@ -160,6 +317,7 @@ impl ToString for TagType {
 ///     tag_type: TagType::Medic, // Set the tag type
 ///     selected: true,           // Set the selection status
 ///     bounding_box,             // Use the defined bounding box
+///     recognition,              // The OCR evidence
 /// };
 ///
 /// println!("Tag type: {:?}", tag.tag_type);
@@ -175,6 +333,99 @@ pub struct Tag {
     tag_type: TagType,
     selected: bool,
     bounding_box: Rect,
+    recognition: Recognition,
+}
+
+/// The evidence a `Tag` was recognized from.
+///
+/// The values allow consumers to reject or flag doubtful tags, e.g. tags whose match ratio is
+/// barely above the acceptance threshold or close to the runner-up.
+///
+/// # Fields
+/// - `raw_text`: The OCR reading the tag was matched from, with surrounding whitespace
+///   trimmed.
+/// - `ocr_confidence`: The confidence the backend reported for `raw_text`, in `[0, 1]`. For
+///   Tesseract this is the mean word confidence.
+/// - `match_ratio`: The similarity between `raw_text` and the matched tag string, in `[0, 1]`.
+/// - `runner_up`: The second most similar tag and its similarity, if there is one.
+/// - `selection_score`: The mean brightness of the tag box in `[0, 1]`. Boxes at or above
+///   `SELECTED_ACCEPT_THRESH` are selected.
+/// - `from_cache`: Whether the tag was taken from the `TagCache` instead of being read. Cached
+///   tags carry the tag string as raw text and the cached confidence and match ratio.
+/// - `pass`: The name of the reading the tag was taken from: `DEFAULT_PASS`, or the name of
+///   the `OcrPass` that won the vote over a weak match.
+/// - `ambiguous_with`: The tag this box would get in the best alternative assignment, if that
+///   assignment is less than `AMBIGUITY_MARGIN` worse than the chosen one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recognition {
+    raw_text: String,
+    ocr_confidence: f64,
+    match_ratio: f64,
+    runner_up: Option<(TagType, f64)>,
+    selection_score: f64,
+    from_cache: bool,
+    pass: &'static str,
+    ambiguous_with: Option<TagType>,
+}
+
+impl Recognition {
+    /// Creates the evidence of a tag taken from the cache. The raw text is the tag string of
+    /// `language`, and the selection score is left at 0.
+    fn cached(entry: CacheEntry, language: ServerLanguage) -> Self {
+        let tag_string: String = entry.tag_type.to_string();
+        Recognition {
+            raw_text: language
+                .localize(&tag_string)
+                .map_or(tag_string, String::from),
+            ocr_confidence: entry.confidence,
+            match_ratio: entry.match_ratio,
+            runner_up: None,
+            selection_score: 0.0,
+            from_cache: true,
+            pass: DEFAULT_PASS,
+            ambiguous_with: None,
+        }
+    }
+
+    /// Returns the OCR reading the tag was matched from.
+    pub fn raw_text(&self) -> &str {
+        &self.raw_text
+    }
+
+    /// Returns the confidence the OCR backend reported for the reading, in `[0, 1]`.
+    pub fn ocr_confidence(&self) -> f64 {
+        self.ocr_confidence
+    }
+
+    /// Returns the similarity between the reading and the matched tag string, in `[0, 1]`.
+    pub fn match_ratio(&self) -> f64 {
+        self.match_ratio
+    }
+
+    /// Returns the second most similar tag and its similarity, if there is one.
+    pub fn runner_up(&self) -> Option<(TagType, f64)> {
+        self.runner_up
+    }
+
+    /// Returns the mean brightness of the tag box, in `[0, 1]`.
+    pub fn selection_score(&self) -> f64 {
+        self.selection_score
+    }
+
+    /// Returns whether the tag was taken from the cache instead of being read.
+    pub fn from_cache(&self) -> bool {
+        self.from_cache
+    }
+
+    /// Returns the name of the reading the tag was taken from.
+    pub fn pass(&self) -> &'static str {
+        self.pass
+    }
+
+    /// Returns the tag this box could also be, if the tag assignment is ambiguous.
+    pub fn ambiguous_with(&self) -> Option<TagType> {
+        self.ambiguous_with
+    }
 }
 
 /// Represents a tag detected in an image with associated properties.
@@ -195,6 +446,7 @@ impl Tag {
     /// - `selected`: A boolean indicating whether the tag is selected.
     /// - `bounding_box`: A reference to a `Rect` object that defines the bounding box of the
     ///   tag in the image.
+    /// - `recognition`: The evidence the tag was recognized from.
     ///
     /// # Returns
     /// - `Result<Self, errors::TagError>`:
@@ -206,7 +458,7 @@ impl Tag {
     /// let tag_string = "Medic";
     /// let selected = true;
     /// let bounding_box = Rect::new(10, 10, 100, 50);
-    /// match Tag::new(tag_string, selected, &bounding_box) {
+    /// match Tag::new(tag_string, selected, &bounding_box, recognition) {
     ///     Ok(tag) => {
     ///         println!("Created tag: {:?}", tag);
     ///     },
@@ -219,45 +471,25 @@ impl Tag {
         tag_string: &str,
         selected: bool,
         bounding_box: &Rect,
+        recognition: Recognition,
     ) -> Result<Self, errors::TagError> {
-        let tag_type = match tag_string {
-            "Medic" => Ok(TagType::Medic),
-            "Caster" => Ok(TagType::Caster),
-            "Vanguard" => Ok(TagType::Vanguard),
-            "Guard" => Ok(TagType::Guard),
-            "Defender" => Ok(TagType::Defender),
-            "Supporter" => Ok(TagType::Supporter),
-            "Melee" => Ok(TagType::Melee),
-            "Debuff" => Ok(TagType::Debuff),
-            "Fast-Redeploy" | "FastRedeploy" | "Fast Redeploy" => Ok(TagType::FastRedeploy),
-            "Shift" => Ok(TagType::Shift),
-            "Summon" => Ok(TagType::Summon),
-            "Support" => Ok(TagType::Support),
-            "Survival" => Ok(TagType::Survival),
-            "Elemental" => Ok(TagType::Elemental),
-            "Ranged" => Ok(TagType::Ranged),
-            "Dp-Recovery" | "DpRecovery" | "Dp Recovery" => Ok(TagType::DpRecovery),
-            "Starter" => Ok(TagType::Starter),
-            "Slow" => Ok(TagType::Slow),
-            "AoE" => Ok(TagType::AoE),
-            "Sniper" => Ok(TagType::Sniper),
-            "Crowd-Control" | "CrowdControl" | "Crowd Control" => Ok(TagType::CrowdControl),
-            "Healing" => Ok(TagType::Healing),
-            "DPS" => Ok(TagType::DPS),
-            "Nuker" => Ok(TagType::Nuker),
-            "SeniorOperator" | "Senior-Operator" | "Senior Operator" => Ok(TagType::SeniorOperator),
-            "Specialist" => Ok(TagType::Specialist),
-            "Robot" => Ok(TagType::Robot),
-            "Top-Operator" | "TopOperator" | "Top Operator" => Ok(TagType::TopOperator),
-            _ => Err(errors::TagError::InvalidTagString),
-        }?;
+        let tag_type: TagType = tag_string.parse::<TagType>()?;
         Ok(Tag {
             tag_type: tag_type,
             selected: selected,
             bounding_box: bounding_box.clone(),
+            recognition,
         })
     }
 
+    /// Returns the type of the tag.
+    ///
+    /// # Returns
+    /// - `TagType`: The recognized tag type.
+    pub fn tag_type(&self) -> TagType {
+        self.tag_type
+    }
+
     /// Returns whether the tag is selected.
     ///
     /// # Returns
@@ -266,6 +498,14 @@ impl Tag {
         self.selected
     }
 
+    /// Returns the evidence the tag was recognized from.
+    ///
+    /// # Returns
+    /// - `&Recognition`: The raw reading, its confidences and the selection score.
+    pub fn recognition(&self) -> &Recognition {
+        &self.recognition
+    }
+
     /// Returns the bounding box of the tag.
     ///
     /// This method retrieves the bounding box that defines the location of the tag in the image.
@@ -290,12 +530,40 @@ impl Tag {
     }
 }
 
-/// Determines if a specified region of an image is selected based on pixel intensity.
+/// Crops a full screenshot of the tag selection screen down to the tag panel.
 ///
-/// This function checks whether a given rectangular region in an image meets a certain
-/// threshold for selection. It calculates the average pixel intensity of the region and
-/// compares it against a predefined threshold to determine if the region is considered
-/// "selected".
+/// The region is defined by `RECRUITMENT_ROI_VERTICAL` and `RECRUITMENT_ROI_HORIZONTAL`,
+/// which hold the fractions of the screenshot to ignore on each side.
+///
+/// # Parameters
+/// - `image`: A reference to the full screenshot.
+///
+/// # Returns
+/// - `Result<Mat, Box<dyn std::error::Error>>`: An owned copy of the tag panel region.
+///
+/// # Example Usage
+/// ```rust
+/// let screenshot: Mat = ...; // Load a screenshot of the tag selection screen
+/// let panel: Mat = crop_recruitment_roi(&screenshot)?;
+/// let tags = image_to_tags(&panel, &mut backend, &Config::default())?;
+/// ```
+pub fn crop_recruitment_roi(image: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+    let (cols, rows) = (image.cols() as f64, image.rows() as f64);
+    let rect: Rect = Rect::new(
+        (cols * RECRUITMENT_ROI_HORIZONTAL.0) as i32,
+        (rows * RECRUITMENT_ROI_VERTICAL.0) as i32,
+        (cols * (1.0 - RECRUITMENT_ROI_HORIZONTAL.0 - RECRUITMENT_ROI_HORIZONTAL.1)) as i32,
+        (rows * (1.0 - RECRUITMENT_ROI_VERTICAL.0 - RECRUITMENT_ROI_VERTICAL.1)) as i32,
+    );
+    Ok(image.roi(rect)?.try_clone()?)
+}
+
+/// Measures how strongly a specified region of an image looks selected, based on pixel
+/// intensity.
+///
+/// Selected tags are drawn bright. This function calculates the average pixel intensity of
+/// the region, which the caller compares against `SELECTED_ACCEPT_THRESH` to determine if the
+/// region is considered "selected".
 ///
 /// # Parameters
 /// - `image`: A reference to a `Mat` object representing the input image. The image should
@@ -304,9 +572,8 @@ impl Tag {
 ///   image. This rectangle is used to crop the image for analysis.
 ///
 /// # Returns
-/// - `Result<bool, Box<dyn std::error::Error>>`:
-///   - On success, returns a boolean value indicating whether the region is selected (`true`)
-///     or not (`false`).
+/// - `Result<f64, Box<dyn std::error::Error>>`:
+///   - On success, returns the average intensity of the region in `[0, 1]`.
 ///   - On failure, returns an error wrapped in a `Box` trait object, which can represent
 ///     any error type.
 ///
@@ -320,19 +587,15 @@ impl Tag {
 ///    
 /// 3. **Average Intensity Calculation**: The average pixel intensity is computed by dividing
 ///    the total intensity by the area of the cropped region. The area is obtained from the
-///    size of the cropped image.
-///    
-/// 4. **Threshold Comparison**: The average intensity is compared against a predefined
-///    threshold (`SELECTED_ACCEPT_THRESH`). If the average intensity meets or exceeds this
-///    threshold, the region is considered selected.
+///    size of the cropped image, and scaled to `[0, 1]`.
 ///
 /// # Example Usage
 /// ```rust
 /// let image: Mat = ...; // Load or create an image
 /// let rect: Rect = ...; // Define the region of interest
-/// match is_tag_region_selected(&image, &rect) {
-///     Ok(selected) => {
-///         if selected {
+/// match tag_selection_score(&image, &rect) {
+///     Ok(score) => {
+///         if score >= SELECTED_ACCEPT_THRESH {
 ///             println!("The tag region is selected.");
 ///         } else {
 ///             println!("The tag region is not selected.");
@@ -347,26 +610,29 @@ impl Tag {
 /// # Errors
 /// This function may return errors related to image processing operations, such as issues
 /// with cropping the image or calculating pixel intensities.
-fn is_tag_region_selected(image: &Mat, rect: &Rect) -> Result<bool, Box<dyn std::error::Error>> {
+fn tag_selection_score(image: &Mat, rect: &Rect) -> Result<f64, Box<dyn std::error::Error>> {
     let cropped: opencv::boxed_ref::BoxedRef<'_, Mat> = image.roi(*rect)?;
     let total: f64 = opencv::core::sum_elems(&cropped)?
         .get(0)
         .unwrap()
         .to_owned();
-    Ok((total / 255.0 / cropped.size().unwrap().area() as f64) >= SELECTED_ACCEPT_THRESH)
+    Ok(total / 255.0 / cropped.size().unwrap().area() as f64)
 }
 
 /// Extracts tags from an image using Optical Character Recognition (OCR).
 ///
 /// This function processes an input image to detect regions that potentially contain tags,
-/// extracts text from those regions using the Tesseract OCR engine, and creates a vector
+/// extracts text from those regions using an OCR backend, and creates a vector
 /// of `Tag` objects based on the extracted text and selection status.
 ///
 /// # Parameters
 /// - `image`: A reference to a `Mat` object representing the input image from which tags
 ///   will be extracted. The image should be in a color format (e.g., CV_8UC3).
-/// - `tesseract`: A mutable reference to a `TessApi` object, which is the Tesseract OCR
-///   engine instance used for text recognition.
+/// - `backend`: The OCR backend used for text recognition, e.g. a `TesseractBackend`. It reads
+///   the tag crops in `tag_page_seg_mode` and is reset to the default mode afterwards.
+/// - `config`: The active settings. The `preprocess_image` and `preprocess_tag` pipelines are
+///   applied before box detection and before OCR, and `debug_dir` receives their
+///   intermediate images.
 ///
 /// # Returns
 /// - `Result<Vec<Tag>, Box<dyn std::error::Error>>`:
@@ -376,30 +642,45 @@ fn is_tag_region_selected(image: &Mat, rect: &Rect) -> Result<bool, Box<dyn std:
 ///     any error type.
 ///
 /// # Processing Steps
-/// 1. **Color Conversion**: The input image is converted from BGR color space to grayscale
-///    using the `cvt_color` function. This simplifies the image and prepares it for tag
-///    detection.
+/// 1. **Preprocessing**: The input image is passed through the `preprocess_image` pipeline,
+///    which converts it to grayscale by default. If the configured pipeline leaves more than
+///    one channel, the result is converted to grayscale afterwards.
 ///    
 /// 2. **Tag Box Detection**: The function calls `detect_tag_boxes` to identify potential
 ///    rectangular regions in the grayscale image that may contain tags. This function
 ///    returns a vector of rectangles representing the detected tag boxes.
 ///    
-/// 3. **Tag Extraction**: For each detected rectangle:
-///    - The function calls `tag_button_to_string` to extract text from the corresponding
-///      region of the grayscale image. If no valid text is extracted, it continues to the
-///      next rectangle.
-///    - It checks if the tag region is selected using the `is_tag_region_selected` function.
-///    - A new `Tag` object is created using the extracted text, selection status, and rectangle.
-///      If the creation of the `Tag` fails, it is ignored.
+/// 3. **Recognition**: Every detected rectangle is cut out with `tag_crop` and passed through
+///    the `preprocess_tag` pipeline. If the `tag_cache` is enabled, crops whose perceptual
+///    hash is cached are taken from the cache. The remaining crops are handed to the backend
+///    at once with `OcrBackend::recognize_batch`, so a pooled backend can read them
+///    concurrently. Newly recognized tags are added to the cache.
+///    
+/// 4. **Retries**: Crops whose reading matches a tag string with a ratio below
+///    `weak_match_thresh` are cut out and read again with every pass of `RETRY_PASSES`. The
+///    tag is then chosen by `vote` among the default reading and the retries.
 ///    
-/// 4. **Return Tags**: After processing all detected rectangles, the function returns the
+/// 5. **Assignment**: A real tag set never contains the same tag twice, so the matched boxes
+///    are assigned their tags jointly with `assign_tags` instead of each taking its closest
+///    tag string.
+///    
+/// 6. **Tag Extraction**: For each detected rectangle:
+///    - The function calls `match_candidates` to match the readings of its crop against the
+///      tag strings. If no valid text is extracted, it continues to the next rectangle.
+///    - It measures the selection score of the tag region using the `tag_selection_score`
+///      function. Regions scoring at least `SELECTED_ACCEPT_THRESH` are selected.
+///    - A new `Tag` object is created using the extracted text, selection status, rectangle
+///      and the collected `Recognition` evidence. If the creation of the `Tag` fails, it is
+///      ignored.
+///    
+/// 7. **Return Tags**: After processing all detected rectangles, the function returns the
 ///    vector of `Tag` objects.
 ///
 /// # Example Usage
 /// ```rust
 /// let image: Mat = ...; // Load or create an image
-/// let mut tesseract: TessApi = ...; // Initialize Tesseract API
-/// match image_to_tags(&image, &mut tesseract) {
+/// let mut backend: TesseractBackend = ...; // Initialize the OCR backend
+/// match image_to_tags(&image, &mut backend, &Config::default()) {
 ///     Ok(tags) => {
 ///         for tag in tags {
 ///             println!("Detected tag: {:?}", tag);
@@ -413,38 +694,313 @@ fn is_tag_region_selected(image: &Mat, rect: &Rect) -> Result<bool, Box<dyn std:
 ///
 /// # Errors
 /// This function may return errors related to image processing operations, memory allocation,
-/// or OCR processing, such as issues with the input image format, problems with the Tesseract
-/// API, or errors in tag creation.
+/// or OCR processing, such as issues with the input image format, problems with the OCR
+/// backend, or errors in tag creation.
 pub fn image_to_tags(
     image: &Mat,
-    mut tesseract: &mut TessApi,
+    backend: &mut dyn OcrBackend,
+    config: &Config,
 ) -> Result<Vec<Tag>, Box<dyn std::error::Error>> {
-    let mut gray: Mat = Mat::default();
-    _ = imgproc::cvt_color(
-        &image,
-        &mut gray,
-        imgproc::COLOR_BGR2GRAY,
-        0,
-        opencv::core::AlgorithmHint::ALGO_HINT_ACCURATE,
-    );
+    backend.set_page_seg_mode(config.tag_page_seg_mode)?;
+    let tags: Result<Vec<Tag>, Box<dyn std::error::Error>> = recognize_tags(image, backend, config);
+    backend.set_page_seg_mode(PageSegMode::default())?;
+    tags
+}
+
+/// Detects the server language of a tag panel from the script of its tags, and from how well
+/// they match the vocabulary of each language when the script leaves a choice.
+///
+/// # Parameters
+/// - `image`: A reference to the tag panel, as passed to `image_to_tags`.
+/// - `backend`: The OCR backend used for recognition. It is left switched to the candidates
+///   it read last, so callers set the detected language afterwards.
+/// - `config`: The active settings. The candidates are `detect_languages`.
+///
+/// # Returns
+/// - `Result<Option<(ServerLanguage, f64)>, Box<dyn std::error::Error>>`: The best scoring
+///   language and its score, the mean match ratio of the tag boxes in `[0, 1]`, or `None` if
+///   there are no candidates, no tag boxes or the best score is below `TAG_MATCH_THRESH`.
+///
+/// # Processing Steps
+/// 1. **Tag Box Detection**: The panel is preprocessed and its tag boxes are detected with
+///    the widest box size limits of the candidates, see `Config::widest_tag_box_size`.
+///
+/// 2. **Combined Reading**: The backend is switched to all candidates at once with
+///    `set_languages` and to the union of their tag whitelists, and the crops are read once
+///    with `tag_page_seg_mode`. No retries, cache lookups or assignment are done.
+///
+/// 3. **Script Classification**: The letters of the readings are sorted into Hangul, kana,
+///    Han and Latin. If `SCRIPT_SHARE` of them are Hangul or Latin, the language is Korean
+///    or English. Han and kana together mean Japanese if `KANA_SHARE` of the letters are
+///    kana, and Chinese or Japanese otherwise. Anything else leaves every candidate open.
+///
+/// 4. **Scoring**: Readings are matched with `match_candidates` against the vocabulary of
+///    each remaining candidate. A single candidate is scored on the combined reading;
+///    several are each read again with their own language and whitelist. Boxes without a
+///    match score 0. The candidate with the highest mean score wins; on a tie, the earlier
+///    candidate is kept.
+///
+/// # Example Usage
+/// ```rust
+/// if let Some((language, score)) = detect_language(&panel, &mut backend, &config)? {
+///     println!("{} ({:.2})", language, score);
+/// }
+/// ```
+///
+/// # Errors
+/// Returns an error if image processing fails, a language cannot be loaded or recognition
+/// fails.
+pub(crate) fn detect_language(
+    image: &Mat,
+    backend: &mut dyn OcrBackend,
+    config: &Config,
+) -> Result<Option<(ServerLanguage, f64)>, Box<dyn std::error::Error>> {
+    if config.detect_languages.is_empty() {
+        return Ok(None);
+    }
+    backend.set_page_seg_mode(config.tag_page_seg_mode)?;
+    let detected: Result<Option<(ServerLanguage, f64)>, Box<dyn std::error::Error>> =
+        score_languages(image, backend, config);
+    backend.set_page_seg_mode(PageSegMode::default())?;
+    detected
+}
+
+/// Runs the steps of `detect_language` with the backend already set to `tag_page_seg_mode`.
+fn score_languages(
+    image: &Mat,
+    backend: &mut dyn OcrBackend,
+    config: &Config,
+) -> Result<Option<(ServerLanguage, f64)>, Box<dyn std::error::Error>> {
+    let gray: Mat = preprocess_panel(image, config)?;
+    let recs: Vec<Rect> =
+        detect_tag_boxes(&gray, config.widest_tag_box_size(&config.detect_languages))?;
+    if recs.is_empty() {
+        return Ok(None);
+    }
+    let crops: Vec<Mat> = recs
+        .iter()
+        .map(|rec: &Rect| tag_crop(&gray, rec, config))
+        .collect::<Result<Vec<Mat>, Box<dyn std::error::Error>>>()?;
+
+    backend.set_languages(&config.detect_languages)?;
+    backend.set_whitelist(&ServerLanguage::combined_tag_whitelist(&config.detect_languages))?;
+    let combined: Vec<Vec<OcrCandidate>> = backend.recognize_batch(&crops)?;
+    let candidates: Vec<ServerLanguage> = script_candidates(&combined, &config.detect_languages);
+
+    let mut best: Option<(ServerLanguage, f64)> = None;
+    for language in &candidates {
+        let score: f64 = if candidates.len() == 1 {
+            vocabulary_score(&combined, *language, config)
+        } else {
+            backend.set_language(*language)?;
+            backend.set_whitelist(&language.tag_whitelist())?;
+            vocabulary_score(&backend.recognize_batch(&crops)?, *language, config)
+        };
+        if best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((*language, score));
+        }
+    }
+    Ok(best.filter(|(_, score)| *score >= TAG_MATCH_THRESH))
+}
+
+/// The writing systems `script_candidates` tells the server languages apart by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Han,
+    Kana,
+    Hangul,
+}
+
+/// Returns the script of `c`, or `None` for digits, punctuation and other scripts.
+fn script(c: char) -> Option<Script> {
+    match c as u32 {
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7A3 => Some(Script::Hangul),
+        0x3040..=0x30FF | 0xFF66..=0xFF9F => Some(Script::Kana),
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF => Some(Script::Han),
+        0x41..=0x5A | 0x61..=0x7A | 0xFF21..=0xFF3A | 0xFF41..=0xFF5A => Some(Script::Latin),
+        _ => None,
+    }
+}
+
+/// Returns the languages of `languages` the script of the best readings allows, see step 3
+/// of `detect_language`. All of `languages` are returned if the script is mixed, nothing
+/// was read or it points to a language that is not a candidate.
+fn script_candidates(
+    readings: &[Vec<OcrCandidate>],
+    languages: &[ServerLanguage],
+) -> Vec<ServerLanguage> {
+    let scripts: Vec<Script> = readings
+        .iter()
+        .filter_map(|candidates: &Vec<OcrCandidate>| candidates.first())
+        .flat_map(|candidate: &OcrCandidate| candidate.text.chars().filter_map(script))
+        .collect();
+    if scripts.is_empty() {
+        return languages.to_vec();
+    }
+    let share = |of: &[Script]| -> f64 {
+        scripts.iter().filter(|s: &&Script| of.contains(s)).count() as f64 / scripts.len() as f64
+    };
+    let allowed: Vec<ServerLanguage> = if share(&[Script::Hangul]) >= SCRIPT_SHARE {
+        vec![ServerLanguage::Korean]
+    } else if share(&[Script::Latin]) >= SCRIPT_SHARE {
+        vec![ServerLanguage::English]
+    } else if share(&[Script::Han, Script::Kana]) >= SCRIPT_SHARE {
+        if share(&[Script::Kana]) >= KANA_SHARE {
+            vec![ServerLanguage::Japanese]
+        } else {
+            vec![ServerLanguage::Chinese, ServerLanguage::Japanese]
+        }
+    } else {
+        ServerLanguage::ALL.to_vec()
+    };
+    let candidates: Vec<ServerLanguage> = languages
+        .iter()
+        .copied()
+        .filter(|language: &ServerLanguage| allowed.contains(language))
+        .collect();
+    if candidates.is_empty() {
+        languages.to_vec()
+    } else {
+        candidates
+    }
+}
+
+/// Returns the mean match ratio of `readings` against the vocabulary of `language`. Boxes
+/// without a match score 0.
+fn vocabulary_score(
+    readings: &[Vec<OcrCandidate>],
+    language: ServerLanguage,
+    config: &Config,
+) -> f64 {
+    readings
+        .iter()
+        .map(|candidates: &Vec<OcrCandidate>| {
+            match_candidates(candidates, &config.matcher, language)
+                .map_or(0.0, |(_, recognition)| recognition.match_ratio)
+        })
+        .sum::<f64>()
+        / readings.len() as f64
+}
+
+/// Runs the steps of `image_to_tags` with the backend already set to `tag_page_seg_mode`.
+fn recognize_tags(
+    image: &Mat,
+    backend: &mut dyn OcrBackend,
+    config: &Config,
+) -> Result<Vec<Tag>, Box<dyn std::error::Error>> {
+    let gray: Mat = preprocess_panel(image, config)?;
+    let recs: Vec<Rect> = detect_tag_boxes(&gray, config.tag_box_size(config.server_language))?;
+    let crops: Vec<Mat> = recs
+        .iter()
+        .map(|rec: &Rect| tag_crop(&gray, rec, config))
+        .collect::<Result<Vec<Mat>, Box<dyn std::error::Error>>>()?;
+    let cache: Option<&TagCache> = config.tag_cache.as_deref();
+    let keys: Vec<Option<u64>> = match cache {
+        Some(_) => crops
+            .iter()
+            .map(|crop: &Mat| cache::perceptual_hash(crop).map(Some))
+            .collect::<Result<Vec<Option<u64>>, Box<dyn std::error::Error>>>()?,
+        None => vec![None; crops.len()],
+    };
+    let cached: Vec<Option<CacheEntry>> = keys
+        .iter()
+        .map(|key: &Option<u64>| cache?.get((*key)?))
+        .collect();
+    let uncached: Vec<Mat> = crops
+        .into_iter()
+        .zip(&cached)
+        .filter(|(_, entry): &(Mat, &Option<CacheEntry>)| entry.is_none())
+        .map(|(crop, _): (Mat, &Option<CacheEntry>)| crop)
+        .collect();
+    let mut readings = backend.recognize_batch(&uncached)?.into_iter();
+    let mut matches: Vec<Option<(&'static str, Recognition)>> = cached
+        .iter()
+        .map(|entry: &Option<CacheEntry>| match entry {
+            Some(entry) => {
+                let tag_string: String = entry.tag_type.to_string();
+                TAGS_STRINGS
+                    .iter()
+                    .find(|s: &&&str| **s == tag_string)
+                    .map(|s: &&'static str| {
+                        (*s, Recognition::cached(*entry, config.server_language))
+                    })
+            }
+            None => match_candidates(
+                &readings.next().unwrap_or_default(),
+                &config.matcher,
+                config.server_language,
+            ),
+        })
+        .collect();
+
+    let weak: Vec<usize> = (0..matches.len())
+        .filter(|i: &usize| {
+            matches[*i].as_ref().is_some_and(|(_, recognition)| {
+                !recognition.from_cache && recognition.match_ratio < config.weak_match_thresh
+            })
+        })
+        .collect();
+    if !weak.is_empty() {
+        let weak_recs: Vec<Rect> = weak.iter().map(|i: &usize| recs[*i]).collect();
+        let retries: Vec<Vec<(f64, &'static str, Recognition)>> =
+            read_retry_passes(&gray, &weak_recs, backend, config)?;
+        for (i, mut ballots) in weak.into_iter().zip(retries) {
+            if let Some((tag_string, recognition)) = matches[i].take() {
+                ballots.insert(0, (1.0, tag_string, recognition));
+            }
+            matches[i] = vote(ballots);
+        }
+    }
+    assign_tags(&mut matches, &config.matcher, config.server_language);
 
-    let recs: Vec<Rect> = detect_tag_boxes(&gray)?;
     let mut tags: Vec<Tag> = vec![];
-    for rec in recs {
-        let tag_string: Option<String> = tag_button_to_string(&mut tesseract, &gray, &rec).unwrap();
-        if tag_string.is_none() {
+    for ((rec, key), matched) in recs.into_iter().zip(keys).zip(matches) {
+        let Some((tag_string, mut recognition)) = matched else {
             continue;
-        }
-        let is_selected: bool = is_tag_region_selected(image, &rec)?;
-        let tag: Result<Tag, errors::TagError> = Tag::new(&tag_string.unwrap(), is_selected, &rec);
+        };
+        recognition.selection_score = tag_selection_score(image, &rec)?;
+        let is_selected: bool = recognition.selection_score >= SELECTED_ACCEPT_THRESH;
+        let tag: Result<Tag, errors::TagError> =
+            Tag::new(tag_string, is_selected, &rec, recognition);
         match tag {
-            Ok(tag) => tags.push(tag),
+            Ok(tag) => {
+                let trusted: bool = !tag.recognition.from_cache
+                    && tag.recognition.match_ratio >= config.weak_match_thresh
+                    && tag.recognition.ambiguous_with.is_none();
+                if let (Some(cache), Some(key), true) = (cache, key, trusted) {
+                    cache.insert(
+                        key,
+                        CacheEntry {
+                            tag_type: tag.tag_type,
+                            confidence: tag.recognition.ocr_confidence,
+                            match_ratio: tag.recognition.match_ratio,
+                        },
+                    );
+                }
+                tags.push(tag)
+            }
             Err(_) => {}
         }
     }
     Ok(tags)
 }
 
+/// Runs the `preprocess_image` pipeline on the tag panel and makes sure the result is grayscale.
+///
+/// # Errors
+/// Returns an error if a preprocessing stage fails.
+pub(crate) fn preprocess_panel(
+    image: &Mat,
+    config: &Config,
+) -> Result<Mat, Box<dyn std::error::Error>> {
+    let mut gray: Mat = config.preprocess_image.run(image, config.debug_dir())?;
+    if gray.channels() != 1 {
+        gray = Grayscale.apply(&gray)?;
+    }
+    Ok(gray)
+}
+
 /// Detects rectangular tag boxes in a given grayscale image.
 ///
 /// This function processes a grayscale image to identify and return a vector of rectangles
@@ -456,6 +1012,8 @@ pub fn image_to_tags(
 /// # Parameters
 /// - `grayscale`: A reference to a `Mat` object representing the input grayscale image.
 ///   The image should be in a single-channel format (e.g., CV_8UC1).
+/// - `box_size`: The minimum and maximum area of a tag box relative to the area of the
+///   image, as returned by `Config::tag_box_size`.
 ///
 /// # Returns
 /// - `Result<Vec<Rect>, Box<dyn std::error::Error>>`:
@@ -478,18 +1036,14 @@ pub fn image_to_tags(
 ///    
 /// 4. **Bounding Box Filtering**: The bounding rectangle of the approximated polygon is
 ///    calculated. The function checks if the area of the bounding box is within specified
-///    limits defined by `MIN_TAG_BOX_SIZE` and `MAX_TAG_BOX_SIZE`, relative to the area
-///    of the input image. Only bounding boxes that meet these criteria are included in the
-///    final result.
-///
-/// # Constants
-/// - `MIN_TAG_BOX_SIZE`: A constant representing the minimum size ratio for valid tag boxes.
-/// - `MAX_TAG_BOX_SIZE`: A constant representing the maximum size ratio for valid tag boxes.
+///    limits given by `box_size`, relative to the area of the input image. The limits depend
+///    on the server language, because the button widths differ between clients. Only
+///    bounding boxes that meet these criteria are included in the final result.
 ///
 /// # Example Usage
 /// ```rust
 /// let grayscale_image: Mat = ...; // Load or create a grayscale image
-/// match detect_tag_boxes(&grayscale_image) {
+/// match detect_tag_boxes(&grayscale_image, config.tag_box_size(config.server_language)) {
 ///     Ok(tag_boxes) => {
 ///         for box in tag_boxes {
 ///             println!("Detected tag box: {:?}", box);
@@ -504,7 +1058,11 @@ pub fn image_to_tags(
 /// # Errors
 /// This function may return errors related to image processing operations, such as
 /// issues with the input image format or memory allocation failures.
-fn detect_tag_boxes(grayscale: &Mat) -> Result<Vec<Rect>, Box<dyn std::error::Error>> {
+pub(crate) fn detect_tag_boxes(
+    grayscale: &Mat,
+    box_size: (f64, f64),
+) -> Result<Vec<Rect>, Box<dyn std::error::Error>> {
+    let (min_box_size, max_box_size): (f64, f64) = box_size;
     let mut threshed: Mat = Mat::default();
     imgproc::threshold(
         &grayscale,
@@ -531,8 +1089,8 @@ fn detect_tag_boxes(grayscale: &Mat) -> Result<Vec<Rect>, Box<dyn std::error::Er
             if poly.len() == 4 {
                 let bounding: Rect = imgproc::bounding_rect(&poly).unwrap();
 
-                if MAX_TAG_BOX_SIZE * (img_size.area() as f64) > bounding.area() as f64
-                    && bounding.area() as f64 >= (MIN_TAG_BOX_SIZE * (img_size.area() as f64))
+                if max_box_size * (img_size.area() as f64) > bounding.area() as f64
+                    && bounding.area() as f64 >= (min_box_size * (img_size.area() as f64))
                 {
                     Some(bounding)
                 } else {
@@ -546,117 +1104,578 @@ fn detect_tag_boxes(grayscale: &Mat) -> Result<Vec<Rect>, Box<dyn std::error::Er
     return Ok(boxes);
 }
 
-/// Extracts text from a specified region of an image using Optical Character Recognition (OCR).
-///
-/// This function takes an image and a rectangle defining a region of interest (ROI),
-/// crops the image to that region, applies thresholding, and then uses the Tesseract OCR
-/// engine to extract text from the cropped image. The extracted text is then compared
-/// against a predefined list of tag strings to find the closest match.
+/// Matches the readings of a tag crop against the known tag strings.
 ///
 /// # Parameters
-/// - `tess`: A mutable reference to a `TessApi` object, which is the Tesseract OCR engine
-///   instance used for text recognition.
-/// - `image`: A reference to a `Mat` object representing the input image from which text
-///   will be extracted.
-/// - `rect`: A reference to a `Rect` object that defines the region of interest in the
-///   image. The rectangle is used to crop the image before performing OCR.
+/// - `candidates`: The readings an OCR backend returned for one tag crop, best first.
+/// - `matcher`: How the readings are compared with the tag strings.
+/// - `language`: The server language, which decides the tag strings the readings are
+///   compared with and may override `matcher`.
 ///
 /// # Returns
-/// - `Result<Option<String>, Box<dyn std::error::Error>>`:
-///   - On success, returns an `Option<String>`. If a valid tag string is found, it returns
-///     `Some(tag_string)`. If no valid tag string is found or if the extracted text is
-///     too short, it returns `None`.
-///   - On failure, returns an error wrapped in a `Box` trait object, which can represent
-///     any error type.
+/// - `Option<(&'static str, Recognition)>`: The entry of `TAGS_STRINGS` closest to the first
+///   matching candidate together with the evidence of the match, or `None` if no candidate is
+///   close to any tag string. The selection score of the returned `Recognition` is left at 0
+///   for the caller to fill in.
 ///
 /// # Processing Steps
-/// 1. **Region of Interest (ROI) Calculation**: The function calculates a cropped region
-///    based on the provided rectangle, adjusting the dimensions to create a margin around
-///    the rectangle.
-///    
-/// 2. **Image Cropping**: The function crops the input image to the specified ROI using
-///    the `roi` method.
-///    
-/// 3. **Thresholding**: A binary threshold is applied to the cropped image to enhance
-///    the contrast between text and background, making it easier for the OCR engine to
-///    recognize the text.
+/// 1. **Text Validation**: Candidates are normalized with `ServerLanguage::normalize`, and
+///    those shorter than `ServerLanguage::min_tag_length` characters are skipped.
 ///    
-/// 4. **Image Encoding**: The thresholded image is encoded into a TIFF format in memory,
-///    which is then used as input for the Tesseract OCR engine.
-///    
-/// 5. **Text Extraction**: The function sets the encoded image to the Tesseract instance
-///    and retrieves the recognized text as a UTF-8 string.
-///    
-/// 6. **Text Validation**: If the extracted text is shorter than three characters, the
-///    function returns `None`.
-///    
-/// 7. **Tag Matching**: The extracted text is compared against a predefined list of tag
-///    strings using the `get_close_matches` function. If a close match is found, it is
-///    returned; otherwise, `None` is returned.
+/// 2. **Tag Matching**: Each remaining candidate is ranked against the tag strings of the
+///    language by similarity ratio with `rank_tag_strings`. The first candidate whose best
+///    ratio reaches `TAG_MATCH_THRESH` is returned together with its runner-up; if no
+///    candidate matches, `None` is returned.
 ///
 /// # Example Usage
 /// ```rust
-/// let mut tess: TessApi = ...; // Initialize Tesseract API
-/// let image: Mat = ...; // Load or create an image
-/// let rect: Rect = ...; // Define the region of interest
-/// match tag_button_to_string(&mut tess, &image, &rect) {
-///     Ok(Some(tag)) => {
-///         println!("Detected tag: {}", tag);
-///     },
-///     Ok(None) => {
-///         println!("No valid tag detected.");
-///     },
-///     Err(e) => {
-///         eprintln!("Error during OCR: {}", e);
-///     }
+/// let candidates: Vec<OcrCandidate> = backend.recognize(&crop)?;
+/// match match_candidates(&candidates, &config.matcher, config.server_language) {
+///     Some((tag, recognition)) => println!("Detected tag: {} ({:?})", tag, recognition),
+///     None => println!("No valid tag detected."),
 /// }
 /// ```
+pub(crate) fn match_candidates(
+    candidates: &[OcrCandidate],
+    matcher: &TagMatcher,
+    language: ServerLanguage,
+) -> Option<(&'static str, Recognition)> {
+    for candidate in candidates {
+        let raw_text: String = language.normalize(&candidate.text);
+        if raw_text.chars().count() < language.min_tag_length() {
+            continue;
+        }
+        let ranked: Vec<(&'static str, f64)> = rank_tag_strings(&raw_text, matcher, language);
+        let Some(&(tag_string, match_ratio)) = ranked.first() else {
+            continue;
+        };
+        if match_ratio < TAG_MATCH_THRESH {
+            continue;
+        }
+        let runner_up: Option<(TagType, f64)> = ranked.get(1).and_then(|(s, ratio)| {
+            s.parse::<TagType>()
+                .ok()
+                .map(|tag_type: TagType| (tag_type, *ratio))
+        });
+        return Some((
+            tag_string,
+            Recognition {
+                raw_text,
+                ocr_confidence: candidate.confidence,
+                match_ratio,
+                runner_up,
+                selection_score: 0.0,
+                from_cache: false,
+                pass: DEFAULT_PASS,
+                ambiguous_with: None,
+            },
+        ));
+    }
+    None
+}
+
+/// Assigns the matched boxes distinct tags so that the total similarity between their raw
+/// texts and the assigned tag strings is maximal.
+///
+/// # Processing Steps
+/// 1. **Similarity Matrix**: The raw text of every matched box is compared with every tag
+///    string using `matcher`.
+///
+/// 2. **Assignment**: `assignment::assign` picks one tag string per box without duplicates.
+///    The match ratio and runner-up of each box are updated to the assigned tag.
+///
+/// 3. **Ambiguity**: For every box, the assignment is solved again with its tag forbidden. If
+///    the total similarity drops by less than `AMBIGUITY_MARGIN`, the tag the box gets in that
+///    alternative is recorded in `Recognition::ambiguous_with`.
+///
+/// 4. **Rejection**: A box that lost its closest tag to another box may be left with a tag
+///    string its text hardly resembles. Boxes whose assigned similarity is below
+///    `TAG_MATCH_THRESH` are dropped, like readings that match no tag at all.
+fn assign_tags(
+    matches: &mut [Option<(&'static str, Recognition)>],
+    matcher: &TagMatcher,
+    language: ServerLanguage,
+) {
+    let similarity: Vec<Vec<f64>> = matches
+        .iter()
+        .flatten()
+        .map(|(_, recognition): &(&'static str, Recognition)| {
+            tag_similarities(&recognition.raw_text, matcher, language)
+        })
+        .collect();
+    let Some((assigned, total)) = assignment::assign(&similarity) else {
+        return;
+    };
+    // Forbidden cells are cheaper than any assignment that avoids them.
+    let forbidden: f64 = -(similarity.len() as f64) - 1.0;
+
+    for (row, (tag_string, recognition)) in matches.iter_mut().flatten().enumerate() {
+        let column: usize = assigned[row];
+        let mut alternative: Vec<Vec<f64>> = similarity.clone();
+        alternative[row][column] = forbidden;
+        recognition.ambiguous_with = assignment::assign(&alternative)
+            .filter(|(_, alternative_total): &(Vec<usize>, f64)| {
+                total - alternative_total < AMBIGUITY_MARGIN
+            })
+            .and_then(|(columns, _): (Vec<usize>, f64)| TAGS_STRINGS[columns[row]].parse().ok());
+
+        *tag_string = TAGS_STRINGS[column];
+        recognition.match_ratio = similarity[row][column];
+        recognition.runner_up = TAGS_STRINGS
+            .iter()
+            .zip(&similarity[row])
+            .enumerate()
+            .filter(|(j, _)| *j != column)
+            .max_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+            .and_then(|(_, (s, ratio))| {
+                s.parse::<TagType>()
+                    .ok()
+                    .map(|tag_type: TagType| (tag_type, *ratio))
+            });
+    }
+    for matched in matches.iter_mut() {
+        if matched
+            .as_ref()
+            .is_some_and(|(_, recognition)| recognition.match_ratio < TAG_MATCH_THRESH)
+        {
+            *matched = None;
+        }
+    }
+}
+
+/// Returns the similarity ratio of `text` to every tag string of `language`, in the order of
+/// `TAGS_STRINGS`.
+fn tag_similarities(text: &str, matcher: &TagMatcher, language: ServerLanguage) -> Vec<f64> {
+    let matcher: &TagMatcher = language.matcher(matcher);
+    language
+        .tag_strings()
+        .iter()
+        .map(|tag_string: &&'static str| matcher.similarity(tag_string, text))
+        .collect()
+}
+
+/// Returns every entry of `TAGS_STRINGS` with the similarity ratio of its `language` string to
+/// `text`, most similar first.
+fn rank_tag_strings(
+    text: &str,
+    matcher: &TagMatcher,
+    language: ServerLanguage,
+) -> Vec<(&'static str, f64)> {
+    let mut ranked: Vec<(&'static str, f64)> = TAGS_STRINGS
+        .iter()
+        .copied()
+        .zip(tag_similarities(text, matcher, language))
+        .collect();
+    ranked.sort_by(|a: &(&str, f64), b: &(&str, f64)| b.1.total_cmp(&a.1));
+    ranked
+}
+
+/// Reads the tag crops of weakly matched boxes again with every pass of `RETRY_PASSES`.
+///
+/// # Parameters
+/// - `gray`: The preprocessed grayscale panel.
+/// - `recs`: The tag boxes to read again.
+/// - `backend`: The OCR backend. Its page segmentation mode is restored to
+///   `tag_page_seg_mode` afterwards.
+/// - `config`: The active settings, for the matcher and the page segmentation mode.
+///
+/// # Returns
+/// - `Result<Vec<Vec<(f64, &'static str, Recognition)>>, Box<dyn std::error::Error>>`: For
+///   every box, the weighted tag matches of the passes that produced one.
 ///
 /// # Errors
-/// This function may return errors related to image processing operations, memory allocation,
-/// or OCR processing, such as issues with the input image format or problems with the Tesseract
-/// API.
+/// Returns an error if a crop cannot be cut out or recognition fails.
+fn read_retry_passes(
+    gray: &Mat,
+    recs: &[Rect],
+    backend: &mut dyn OcrBackend,
+    config: &Config,
+) -> Result<Vec<Vec<(f64, &'static str, Recognition)>>, Box<dyn std::error::Error>> {
+    let mut ballots: Vec<Vec<(f64, &'static str, Recognition)>> = vec![vec![]; recs.len()];
+    for pass in &RETRY_PASSES {
+        let crops: Vec<Mat> = recs
+            .iter()
+            .map(|rec: &Rect| pass_crop(gray, rec, pass))
+            .collect::<Result<Vec<Mat>, Box<dyn std::error::Error>>>()?;
+        backend.set_page_seg_mode(pass.page_seg_mode)?;
+        let readings: Vec<Vec<OcrCandidate>> = backend.recognize_batch(&crops)?;
+        for (box_ballots, candidates) in ballots.iter_mut().zip(readings) {
+            if let Some((tag_string, mut recognition)) =
+                match_candidates(&candidates, &config.matcher, config.server_language)
+            {
+                recognition.pass = pass.name;
+                box_ballots.push((pass.weight, tag_string, recognition));
+            }
+        }
+    }
+    backend.set_page_seg_mode(config.tag_page_seg_mode)?;
+    Ok(ballots)
+}
 
-fn tag_button_to_string(
-    tess: &mut TessApi,
+/// Chooses a tag among several readings of the same crop by weighted voting.
+///
+/// Every ballot votes for its tag string with its weight times its match ratio times its OCR
+/// confidence. The tag string with the highest total wins, and the winning `Recognition` is
+/// the one of its strongest single vote. Ties go to the earlier ballot.
+///
+/// # Returns
+/// - `Option<(&'static str, Recognition)>`: The winning tag string and evidence, or `None` if
+///   there are no ballots.
+fn vote(ballots: Vec<(f64, &'static str, Recognition)>) -> Option<(&'static str, Recognition)> {
+    let score = |(weight, _, recognition): &(f64, &'static str, Recognition)| -> f64 {
+        weight * recognition.match_ratio * recognition.ocr_confidence
+    };
+    let mut totals: Vec<(&'static str, f64)> = vec![];
+    for ballot in &ballots {
+        match totals.iter_mut().find(|(s, _)| *s == ballot.1) {
+            Some((_, total)) => *total += score(ballot),
+            None => totals.push((ballot.1, score(ballot))),
+        }
+    }
+    let (winner, _) = totals.into_iter().fold(
+        None,
+        |best: Option<(&'static str, f64)>, (s, total): (&'static str, f64)| match best {
+            Some((_, best_total)) if best_total >= total => best,
+            _ => Some((s, total)),
+        },
+    )?;
+    ballots
+        .into_iter()
+        .filter(|(_, s, _)| *s == winner)
+        .fold(
+            None,
+            |best: Option<(f64, &'static str, Recognition)>,
+             ballot: (f64, &'static str, Recognition)| match best {
+                Some(best) if score(&best) >= score(&ballot) => Some(best),
+                _ => Some(ballot),
+            },
+        )
+        .map(|(_, s, recognition)| (s, recognition))
+}
+
+/// Shrinks a tag box by `inset` of its width and height on every side, so the button border
+/// is not part of the crop.
+fn inset_rect(rect: &Rect, inset: f64) -> Rect {
+    Rect::new(
+        rect.x + (inset * (rect.width as f64)) as i32,
+        rect.y + (inset * (rect.height as f64)) as i32,
+        rect.width - (2.0 * inset * (rect.width as f64)) as i32,
+        rect.height - (2.0 * inset * (rect.height as f64)) as i32,
+    )
+}
+
+/// Cuts a tag box out of the grayscale panel with a `TAG_CROP_INSET` inset and runs the
+/// `preprocess_tag` pipeline on it.
+///
+/// # Errors
+/// Returns an error if the box lies outside the image or a preprocessing stage fails.
+pub(crate) fn tag_crop(
     image: &Mat,
     rect: &Rect,
-) -> Result<Option<String>, Box<dyn std::error::Error>> {
-    let (x, y, w, h) = (
-        rect.x + (0.05 * (rect.width as f64)) as i32,
-        rect.y + (0.05 * (rect.height as f64)) as i32,
-        rect.width - (0.1 * (rect.width as f64)) as i32,
-        rect.height - (0.1 * (rect.height as f64)) as i32,
-    );
-    let cropped: opencv::boxed_ref::BoxedRef<'_, Mat> = image.roi(Rect::new(x, y, w, h))?;
-    let mut threshed: Mat = Mat::default();
-    imgproc::threshold(
-        &cropped,
-        &mut threshed,
-        160.0,
-        255.0,
-        imgproc::THRESH_BINARY,
-    )?;
-    let mut buffer: Vector<u8> = Vector::new();
-    imgcodecs::imencode(
-        ".tiff",
-        &threshed,
-        &mut buffer,
-        &opencv::core::Vector::new(),
-    )?;
-    let pix: leptess::leptonica::Pix = leptess::leptonica::pix_read_mem(&buffer.as_slice())?;
-    tess.set_image(&pix);
-    let tag_string: String = tess.get_utf8_text()?;
+    config: &Config,
+) -> Result<Mat, Box<dyn std::error::Error>> {
+    let cropped: Mat = image.roi(inset_rect(rect, TAG_CROP_INSET))?.try_clone()?;
+    config.preprocess_tag.run(&cropped, config.debug_dir())
+}
 
-    if tag_string.len() < 3 {
-        return Ok(None);
+/// Cuts a tag box out of the grayscale panel the way `pass` prescribes: with its inset,
+/// scaled by its factor and binarized with its threshold.
+///
+/// # Errors
+/// Returns an error if the box lies outside the image or the crop cannot be processed.
+fn pass_crop(image: &Mat, rect: &Rect, pass: &OcrPass) -> Result<Mat, Box<dyn std::error::Error>> {
+    let mut cropped: Mat = image.roi(inset_rect(rect, pass.inset))?.try_clone()?;
+    if pass.scale != 1.0 {
+        cropped = Upscale { factor: pass.scale }.apply(&cropped)?;
     }
+    let mode: ThresholdMode = match pass.threshold {
+        Some(value) => ThresholdMode::Fixed(value),
+        None => ThresholdMode::Otsu,
+    };
+    Threshold(mode).apply(&cropped)
+}
 
-    let a: Vec<&str> = get_close_matches(&tag_string, TAGS_STRINGS.into(), 1, 0.5);
-    let v: Option<&&str> = a.get(0);
-    if v.is_none() {
-        return Ok(None);
+#[cfg(test)]
+impl Tag {
+    /// Creates a tag of `tag_type` with empty evidence, for tests of code that consumes tags.
+    pub(crate) fn with_type(tag_type: TagType, selected: bool) -> Self {
+        Tag {
+            tag_type,
+            selected,
+            bounding_box: Rect::default(),
+            recognition: Recognition {
+                raw_text: tag_type.to_string(),
+                ocr_confidence: 1.0,
+                match_ratio: 1.0,
+                runner_up: None,
+                selection_score: 0.0,
+                from_cache: false,
+                pass: DEFAULT_PASS,
+                ambiguous_with: None,
+            },
+        }
+    }
+}
+
+/// Draws a white tag panel with five dark tag buttons of the given width, laid out like the
+/// client does.
+#[cfg(test)]
+fn tag_panel_fixture(button_width: i32) -> Result<Mat, Box<dyn std::error::Error>> {
+    let mut panel: Mat = Mat::new_rows_cols_with_default(
+        300,
+        600,
+        opencv::core::CV_8UC3,
+        opencv::core::Scalar::all(255.0),
+    )?;
+    for i in 0..5 {
+        let rect: Rect = Rect::new(20 + (i % 3) * 190, 40 + (i / 3) * 120, button_width, 50);
+        imgproc::rectangle(
+            &mut panel,
+            rect,
+            opencv::core::Scalar::all(60.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+    }
+    Ok(panel)
+}
+
+#[test]
+fn image_to_tags_with_mock_backend() -> Result<(), Box<dyn std::error::Error>> {
+    let panel: Mat = tag_panel_fixture(160)?;
+    let mut backend: super::ocr::MockBackend =
+        super::ocr::MockBackend::new(&["Snlper", "Suport", "Supporter", "Guard", "Medic"]);
+    let tags: Vec<Tag> = image_to_tags(&panel, &mut backend, &Config::default())?;
+    assert_eq!(tags.len(), 5);
+    assert!(tags.iter().all(|tag: &Tag| !tag.selected()));
+    let mut tag_types: Vec<TagType> = tags.iter().map(Tag::tag_type).collect();
+    tag_types.sort();
+    let mut expected: Vec<TagType> = vec![
+        TagType::Sniper,
+        TagType::Support,
+        TagType::Supporter,
+        TagType::Guard,
+        TagType::Medic,
+    ];
+    expected.sort();
+    assert_eq!(tag_types, expected);
+    let sniper: &Tag = tags
+        .iter()
+        .find(|tag: &&Tag| tag.tag_type() == TagType::Sniper)
+        .unwrap();
+    let recognition: &Recognition = sniper.recognition();
+    assert_eq!(recognition.raw_text(), "Snlper");
+    assert!(recognition.match_ratio() < 1.0 && recognition.match_ratio() >= TAG_MATCH_THRESH);
+    Ok(())
+}
+
+#[test]
+fn image_to_tags_with_japanese_mock_backend() -> Result<(), Box<dyn std::error::Error>> {
+    let panel: Mat = tag_panel_fixture(185)?;
+    let mut backend: super::ocr::MockBackend = super::ocr::MockBackend::new(&[
+        "狙撃タイブ",
+        "支 援",
+        "補助タイプ",
+        "前衛タイプ",
+        "ＣＯＳＴ回復",
+    ]);
+    let config: Config = Config {
+        server_language: ServerLanguage::Japanese,
+        ..Config::default()
+    };
+    let tags: Vec<Tag> = image_to_tags(&panel, &mut backend, &config)?;
+    let mut tag_types: Vec<TagType> = tags.iter().map(Tag::tag_type).collect();
+    tag_types.sort();
+    let mut expected: Vec<TagType> = vec![
+        TagType::Sniper,
+        TagType::Support,
+        TagType::Supporter,
+        TagType::Guard,
+        TagType::DpRecovery,
+    ];
+    expected.sort();
+    assert_eq!(tag_types, expected);
+    let sniper: &Tag = tags
+        .iter()
+        .find(|tag: &&Tag| tag.tag_type() == TagType::Sniper)
+        .unwrap();
+    assert_eq!(sniper.recognition().raw_text(), "狙撃タイブ");
+    Ok(())
+}
+
+#[test]
+fn detect_language_keeps_best_matching_vocabulary() -> Result<(), Box<dyn std::error::Error>> {
+    let panel: Mat = tag_panel_fixture(160)?;
+    let mut backend: super::ocr::MockBackend = super::ocr::MockBackend::new(&[
+        "스나이퍼 타입",
+        "지원",
+        "서포터 타입",
+        "가드 타입",
+        "메딕 타입",
+    ]);
+    let config: Config = Config {
+        detect_languages: ServerLanguage::ALL.to_vec(),
+        ..Config::default()
+    };
+    let (language, score) = detect_language(&panel, &mut backend, &config)?.unwrap();
+    assert_eq!(language, ServerLanguage::Korean);
+    assert!((score - 1.0).abs() < 1e-9);
+    Ok(())
+}
+
+#[test]
+fn detect_language_falls_back_to_vocabularies_for_han() -> Result<(), Box<dyn std::error::Error>> {
+    let panel: Mat = tag_panel_fixture(160)?;
+    let mut backend: super::ocr::MockBackend = super::ocr::MockBackend::new(&[
+        "医疗干员",
+        "先锋干员",
+        "削弱",
+        "快速复活",
+        "狙击干员",
+    ]);
+    let config: Config = Config {
+        detect_languages: ServerLanguage::ALL.to_vec(),
+        ..Config::default()
+    };
+    let (language, score) = detect_language(&panel, &mut backend, &config)?.unwrap();
+    assert_eq!(language, ServerLanguage::Chinese);
+    assert!((score - 1.0).abs() < 1e-9);
+    Ok(())
+}
+
+#[test]
+fn detect_language_rejects_weak_vocabulary_matches() -> Result<(), Box<dyn std::error::Error>> {
+    let panel: Mat = tag_panel_fixture(160)?;
+    let mut backend: super::ocr::MockBackend = super::ocr::MockBackend::new(&["xqzv", "kjw"]);
+    let config: Config = Config {
+        detect_languages: ServerLanguage::ALL.to_vec(),
+        ..Config::default()
+    };
+    assert_eq!(detect_language(&panel, &mut backend, &config)?, None);
+    Ok(())
+}
+
+#[test]
+fn script_candidates_tell_languages_apart_by_script() {
+    let readings = |texts: &[&str]| -> Vec<Vec<OcrCandidate>> {
+        texts
+            .iter()
+            .map(|text: &&str| {
+                vec![OcrCandidate {
+                    text: text.to_string(),
+                    confidence: 1.0,
+                }]
+            })
+            .collect()
+    };
+    let all: &[ServerLanguage] = &ServerLanguage::ALL;
+    assert_eq!(
+        script_candidates(&readings(&["가드 타입", "메딕 타입"]), all),
+        vec![ServerLanguage::Korean]
+    );
+    assert_eq!(
+        script_candidates(&readings(&["Guard", "Medic"]), all),
+        vec![ServerLanguage::English]
+    );
+    assert_eq!(
+        script_candidates(&readings(&["狙撃タイプ", "医療タイプ"]), all),
+        vec![ServerLanguage::Japanese]
+    );
+    assert_eq!(
+        script_candidates(&readings(&["医疗干员", "削弱"]), all),
+        vec![ServerLanguage::Chinese, ServerLanguage::Japanese]
+    );
+    assert_eq!(
+        script_candidates(&readings(&["Guard", "가드"]), all),
+        ServerLanguage::ALL.to_vec()
+    );
+    assert_eq!(
+        script_candidates(&readings(&["가드 타입"]), &[ServerLanguage::English]),
+        vec![ServerLanguage::English]
+    );
+    assert_eq!(script_candidates(&readings(&["123"]), all), ServerLanguage::ALL.to_vec());
+}
+
+#[test]
+fn vote_prefers_agreeing_passes_over_single_reading() {
+    let recognition = |raw_text: &str, match_ratio: f64, pass: &'static str| Recognition {
+        raw_text: raw_text.into(),
+        ocr_confidence: 0.8,
+        match_ratio,
+        runner_up: None,
+        selection_score: 0.0,
+        from_cache: false,
+        pass,
+        ambiguous_with: None,
+    };
+    let ballots: Vec<(f64, &'static str, Recognition)> = vec![
+        (1.0, "Slow", recognition("Sllow", 0.89, DEFAULT_PASS)),
+        (1.0, "Shift", recognition("Shitt", 0.6, "otsu")),
+        (0.8, "Shift", recognition("Shift", 1.0, "threshold-120")),
+    ];
+    let (tag_string, winner) = vote(ballots).unwrap();
+    assert_eq!(tag_string, "Shift");
+    assert_eq!(winner.pass(), "threshold-120");
+    assert!(vote(vec![]).is_none());
+}
+
+#[test]
+fn assign_tags_drops_boxes_left_with_dissimilar_tags() {
+    let recognition = |raw_text: &str| Recognition {
+        raw_text: raw_text.into(),
+        ocr_confidence: 0.8,
+        match_ratio: 1.0,
+        runner_up: None,
+        selection_score: 0.0,
+        from_cache: false,
+        pass: DEFAULT_PASS,
+        ambiguous_with: None,
+    };
+    // Both boxes read "Robot"; the one that does not get it is left with a tag string far
+    // below `TAG_MATCH_THRESH`.
+    let mut matches: Vec<Option<(&'static str, Recognition)>> = vec![
+        Some(("Robot", recognition("Robot"))),
+        Some(("Robot", recognition("Robot"))),
+        Some(("Healing", recognition("Healing"))),
+    ];
+    assign_tags(&mut matches, &TagMatcher::Difflib, ServerLanguage::English);
+    let assigned: Vec<Option<&str>> = matches
+        .iter()
+        .map(|matched: &Option<(&'static str, Recognition)>| matched.as_ref().map(|(s, _)| *s))
+        .collect();
+    assert_eq!(assigned.iter().filter(|s| **s == Some("Robot")).count(), 1);
+    assert_eq!(assigned.iter().filter(|s| s.is_none()).count(), 1);
+    assert_eq!(assigned[2], Some("Healing"));
+}
+
+#[test]
+fn tag_box_size_limits_follow_the_server_language() -> Result<(), Box<dyn std::error::Error>> {
+    // Two buttons of 250 x 200 on a 600 x 300 panel cover 0.28 of it each: too large for the
+    // English limits, but within the Japanese ones.
+    let mut gray: Mat = Mat::new_rows_cols_with_default(
+        300,
+        600,
+        opencv::core::CV_8UC1,
+        opencv::core::Scalar::all(255.0),
+    )?;
+    for x in [20, 300] {
+        imgproc::rectangle(
+            &mut gray,
+            Rect::new(x, 50, 250, 200),
+            opencv::core::Scalar::all(60.0),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
     }
-    let s: String = v.unwrap().to_string().clone();
-    Ok(Some(s))
+    let mut config: Config = Config::default();
+    assert!(detect_tag_boxes(&gray, config.tag_box_size(ServerLanguage::English))?.is_empty());
+    assert_eq!(detect_tag_boxes(&gray, config.tag_box_size(ServerLanguage::Japanese))?.len(), 2);
+
+    config.set("tag_box_size.en", "0.005, 0.3")?;
+    assert_eq!(detect_tag_boxes(&gray, config.tag_box_size(ServerLanguage::English))?.len(), 2);
+    config.set("tag_box_size.jp", "0.005, 0.25")?;
+    assert!(detect_tag_boxes(&gray, config.tag_box_size(ServerLanguage::Japanese))?.is_empty());
+    Ok(())
 }
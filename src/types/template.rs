@@ -0,0 +1,168 @@
+use opencv::{
+    core::{self, Mat, Point, Rect, Size, Vector},
+    imgcodecs, imgproc,
+    prelude::MatTraitConst,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// Directory holding the reference crops used for template matching.
+pub(crate) static TEMPLATE_DIR: &str = "templates";
+/// Screen height the reference crops were captured at.
+static TEMPLATE_REFERENCE_HEIGHT: f64 = 1080.0;
+
+/// Templates loaded so far, by file name and screen height. A missing file is remembered as
+/// `None`, so it is not looked up again on every scan.
+static LOADED_TEMPLATES: OnceLock<Mutex<HashMap<(String, i32), Option<Arc<Mat>>>>> =
+    OnceLock::new();
+
+/// Loads a grayscale template from `TEMPLATE_DIR` and scales it to the given screen height.
+///
+/// Templates are captured from 1080p screenshots, so they are resized by
+/// `screen_height / TEMPLATE_REFERENCE_HEIGHT` to match the screenshot being searched. Every
+/// template is read from disk once per screen height and then served from `LOADED_TEMPLATES`.
+///
+/// # Parameters
+/// - `name`: The file name of the template inside `TEMPLATE_DIR`.
+/// - `screen_height`: The height in pixels of the full screenshot that will be searched.
+///
+/// # Returns
+/// - `Result<Option<Arc<Mat>>, Box<dyn std::error::Error>>`: The scaled template, or `None`
+///   if the file does not exist or cannot be decoded.
+pub(crate) fn load_template(
+    name: &str,
+    screen_height: i32,
+) -> Result<Option<Arc<Mat>>, Box<dyn std::error::Error>> {
+    let loaded: &Mutex<HashMap<(String, i32), Option<Arc<Mat>>>> =
+        LOADED_TEMPLATES.get_or_init(|| Mutex::new(HashMap::new()));
+    let key: (String, i32) = (name.to_string(), screen_height);
+    if let Some(template) = loaded.lock().unwrap().get(&key) {
+        return Ok(template.clone());
+    }
+    let template: Option<Arc<Mat>> =
+        read_template(&Path::new(TEMPLATE_DIR).join(name), screen_height)?.map(Arc::new);
+    loaded.lock().unwrap().insert(key, template.clone());
+    Ok(template)
+}
+
+/// Reads a grayscale template from `path` and scales it to the given screen height, see
+/// `load_template`.
+fn read_template(
+    path: &Path,
+    screen_height: i32,
+) -> Result<Option<Mat>, Box<dyn std::error::Error>> {
+    let template: Mat = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_GRAYSCALE)?;
+    if template.empty() {
+        return Ok(None);
+    }
+    let scale: f64 = screen_height as f64 / TEMPLATE_REFERENCE_HEIGHT;
+    let mut scaled: Mat = Mat::default();
+    imgproc::resize(
+        &template,
+        &mut scaled,
+        Size::default(),
+        scale,
+        scale,
+        imgproc::INTER_AREA,
+    )?;
+    Ok(Some(scaled))
+}
+
+/// Finds the best match of `template` inside `haystack` using normalized cross-correlation.
+///
+/// # Parameters
+/// - `haystack`: The grayscale image to search.
+/// - `template`: The grayscale template to look for. It must not be larger than `haystack`.
+///
+/// # Returns
+/// - `Result<Option<(Rect, f64)>, Box<dyn std::error::Error>>`: The location of the best match
+///   and its score in `[-1, 1]`, or `None` if the template does not fit into the haystack.
+pub(crate) fn best_match(
+    haystack: &Mat,
+    template: &Mat,
+) -> Result<Option<(Rect, f64)>, Box<dyn std::error::Error>> {
+    let haystack_size: Size = haystack.size()?;
+    let template_size: Size = template.size()?;
+    if template_size.width > haystack_size.width || template_size.height > haystack_size.height {
+        return Ok(None);
+    }
+    let mut scores: Mat = Mat::default();
+    imgproc::match_template_def(haystack, template, &mut scores, imgproc::TM_CCOEFF_NORMED)?;
+    let mut max_val: f64 = 0.0;
+    let mut max_loc: Point = Point::default();
+    core::min_max_loc(
+        &scores,
+        None,
+        Some(&mut max_val),
+        None,
+        Some(&mut max_loc),
+        &core::no_array(),
+    )?;
+    Ok(Some((
+        Rect::new(
+            max_loc.x,
+            max_loc.y,
+            template_size.width,
+            template_size.height,
+        ),
+        max_val,
+    )))
+}
+
+/// Cuts a template out of a screenshot and stores it in `TEMPLATE_DIR`.
+///
+/// The templates `retag` looks for, such as the resource icons, are not part of the
+/// repository and are captured from a screenshot of the player's own client with this
+/// function.
+///
+/// # Parameters
+/// - `image`: The full screenshot in BGR color format.
+/// - `rect`: The region of the template in `image`, in pixels.
+/// - `name`: The file name to store the template as, e.g. `recruitment_permit.png`.
+///
+/// # Returns
+/// - `Result<PathBuf, Box<dyn std::error::Error>>`: The path the template was written to.
+///
+/// # Processing Steps
+/// 1. **Cropping**: The region is cut out and converted to grayscale.
+///
+/// 2. **Scaling**: The crop is scaled by `TEMPLATE_REFERENCE_HEIGHT / image height`, so that
+///    `load_template` can scale it to any other screen height.
+///
+/// # Errors
+/// Returns an error if the region does not lie inside the image or the file cannot be
+/// written.
+pub(crate) fn capture_template(
+    image: &Mat,
+    rect: Rect,
+    name: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &image.roi(rect)?,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    let scale: f64 = TEMPLATE_REFERENCE_HEIGHT / image.rows() as f64;
+    let mut scaled: Mat = Mat::default();
+    imgproc::resize(
+        &gray,
+        &mut scaled,
+        Size::default(),
+        scale,
+        scale,
+        imgproc::INTER_AREA,
+    )?;
+    fs::create_dir_all(TEMPLATE_DIR)?;
+    let path: PathBuf = Path::new(TEMPLATE_DIR).join(name);
+    if !imgcodecs::imwrite(&path.to_string_lossy(), &scaled, &Vector::new())? {
+        return Err(format!("{}: could not write template", path.display()).into());
+    }
+    Ok(path)
+}
@@ -0,0 +1,440 @@
+use super::{
+    language::ServerLanguage,
+    ocr::{EngineMode, OcrBackend, OcrCandidate, PageSegMode},
+};
+use leptess::capi;
+use opencv::{
+    core::{Mat, CV_8UC1},
+    prelude::*,
+};
+use std::{
+    ffi::{c_char, CStr, CString},
+    fs,
+    path::{Path, PathBuf},
+    ptr,
+    sync::Arc,
+};
+
+/// Resolution reported to Tesseract for raw frames, which carry no resolution of their own.
+/// Without it Tesseract warns about an invalid resolution on every crop.
+static SOURCE_RESOLUTION: i32 = 70;
+
+/// The Tesseract OCR engine as an `OcrBackend`.
+///
+/// Tesseract returns a single reading per crop; its mean word confidence is reported as the
+/// confidence of that reading.
+///
+/// The engine is driven through the C API re-exported by leptess. `TessApi` can only be
+/// initialized without variables, but some variables, such as `user_words_file`, are only
+/// read during initialization and have to be handed to `TessBaseAPIInit4`. The options are
+/// kept so that the engine can be initialized again when a scan switches the language, or
+/// loads several languages at once to detect it. If that fails, the backend refuses to
+/// recognize anything until it is initialized again. The whitelist and page segmentation mode
+/// are kept as well, because initializing again resets them.
+pub struct TesseractBackend {
+    api: *mut capi::TessBaseAPI,
+    tessdata_dir: String,
+    engine_mode: EngineMode,
+    vocabularies: Option<Arc<Vocabularies>>,
+    variables: Vec<(String, String)>,
+    languages: Vec<ServerLanguage>,
+    whitelist: Option<String>,
+    page_seg_mode: Option<PageSegMode>,
+    initialized: bool,
+}
+
+impl TesseractBackend {
+    /// Initializes Tesseract with the trained data of `language` from `tessdata_dir`, using
+    /// the default engine mode, no user words and no extra variables.
+    ///
+    /// # Errors
+    /// Returns an error if Tesseract cannot be initialized, e.g. because the trained data is
+    /// missing.
+    pub fn new(
+        tessdata_dir: &str,
+        language: ServerLanguage,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_options(tessdata_dir, language, EngineMode::Default, None, &[])
+    }
+
+    /// Initializes Tesseract with the trained data of `language` from `tessdata_dir`.
+    ///
+    /// # Parameters
+    /// - `tessdata_dir`: Directory holding the trained data.
+    /// - `language`: The server language whose trained data is loaded.
+    /// - `engine_mode`: The recognition engine Tesseract runs internally.
+    /// - `vocabularies`: If set, the user words and patterns of the language are passed to
+    ///   Tesseract. They are written once with `Vocabularies::write` and shared by every
+    ///   backend, so backends on different threads never write the files concurrently.
+    /// - `variables`: Tesseract variables as `(name, value)` pairs. They are set during
+    ///   initialization, so variables Tesseract only reads at that point take effect as well.
+    ///
+    /// # Errors
+    /// Returns an error if Tesseract cannot allocate its engine, a name or value contains a
+    /// NUL byte, or if Tesseract cannot be initialized, e.g. because the trained data is
+    /// missing or the engine mode is not supported by it.
+    pub fn with_options(
+        tessdata_dir: &str,
+        language: ServerLanguage,
+        engine_mode: EngineMode,
+        vocabularies: Option<Arc<Vocabularies>>,
+        variables: &[(String, String)],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let api: *mut capi::TessBaseAPI = unsafe { capi::TessBaseAPICreate() };
+        if api.is_null() {
+            return Err("Tesseract could not be created".into());
+        }
+        // The backend owns the handle from here on, so it is deleted on every error path.
+        let mut backend: TesseractBackend = TesseractBackend {
+            api,
+            tessdata_dir: tessdata_dir.into(),
+            engine_mode,
+            vocabularies,
+            variables: variables.to_vec(),
+            languages: vec![language],
+            whitelist: None,
+            page_seg_mode: None,
+            initialized: false,
+        };
+        backend.init(&[language])?;
+        Ok(backend)
+    }
+
+    /// Initializes the engine with the trained data of `languages` and the stored options.
+    /// Tesseract releases the previously loaded data itself.
+    ///
+    /// The user words and patterns are only passed for a single language, since Tesseract
+    /// takes one file of each for all loaded languages.
+    ///
+    /// The backend counts as uninitialized from the start of the call until it succeeds, so a
+    /// failed switch leaves no half loaded engine to read with.
+    ///
+    /// # Errors
+    /// Returns an error if a name or value contains a NUL byte, if Tesseract cannot be
+    /// initialized, or if the last whitelist or page segmentation mode cannot be set again.
+    fn init(&mut self, languages: &[ServerLanguage]) -> Result<(), Box<dyn std::error::Error>> {
+        self.initialized = false;
+        let mut variables: Vec<(String, String)> = match (&self.vocabularies, languages) {
+            (Some(vocabularies), [language]) => vocabularies.variables(*language).to_vec(),
+            _ => vec![],
+        };
+        variables.extend(self.variables.iter().cloned());
+
+        let tessdata_languages: String = languages
+            .iter()
+            .map(|language: &ServerLanguage| language.tessdata_language())
+            .collect::<Vec<&str>>()
+            .join("+");
+        let tessdata_dir: CString = CString::new(self.tessdata_dir.as_str())?;
+        let tessdata_language: CString = CString::new(tessdata_languages.as_str())?;
+        let names: Vec<CString> = variables
+            .iter()
+            .map(|(name, _)| CString::new(name.as_str()))
+            .collect::<Result<Vec<CString>, std::ffi::NulError>>()?;
+        let values: Vec<CString> = variables
+            .iter()
+            .map(|(_, value)| CString::new(value.as_str()))
+            .collect::<Result<Vec<CString>, std::ffi::NulError>>()?;
+        let mut name_ptrs: Vec<*mut c_char> = names
+            .iter()
+            .map(|name: &CString| name.as_ptr() as *mut c_char)
+            .collect();
+        let mut value_ptrs: Vec<*mut c_char> = values
+            .iter()
+            .map(|value: &CString| value.as_ptr() as *mut c_char)
+            .collect();
+
+        let result: i32 = unsafe {
+            capi::TessBaseAPIInit4(
+                self.api,
+                tessdata_dir.as_ptr(),
+                tessdata_language.as_ptr(),
+                self.engine_mode.tesseract_value() as capi::TessOcrEngineMode,
+                ptr::null_mut(),
+                0,
+                name_ptrs.as_mut_ptr(),
+                value_ptrs.as_mut_ptr(),
+                name_ptrs.len(),
+                0,
+            )
+        };
+        if result != 0 {
+            return Err(format!(
+                "Tesseract could not be initialized with language `{}`",
+                tessdata_languages
+            )
+            .into());
+        }
+        self.languages = languages.to_vec();
+        // A language switch rebuilds the engine, which resets every variable set after the
+        // last initialization to Tesseract's default, so the last settings are applied again.
+        if let Some(whitelist) = self.whitelist.clone() {
+            self.set_variable("tessedit_char_whitelist", &whitelist)?;
+        }
+        if let Some(mode) = self.page_seg_mode {
+            self.set_variable("tessedit_pageseg_mode", &mode.tesseract_value().to_string())?;
+        }
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Sets a Tesseract variable after initialization.
+    ///
+    /// # Errors
+    /// Returns an error if Tesseract does not know the variable.
+    fn set_variable(&mut self, name: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let name_cstr: CString = CString::new(name)?;
+        let value_cstr: CString = CString::new(value)?;
+        let accepted: i32 = unsafe {
+            capi::TessBaseAPISetVariable(self.api, name_cstr.as_ptr(), value_cstr.as_ptr())
+        };
+        if accepted == 0 {
+            return Err(format!("Tesseract rejected the variable `{}`", name).into());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TesseractBackend {
+    fn drop(&mut self) {
+        unsafe { capi::TessBaseAPIDelete(self.api) }
+    }
+}
+
+impl OcrBackend for TesseractBackend {
+    /// Sets the whitelist and remembers it for the next language switch.
+    fn set_whitelist(&mut self, chars: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_variable("tessedit_char_whitelist", chars)?;
+        self.whitelist = Some(chars.into());
+        Ok(())
+    }
+
+    /// Sets the mode and remembers it for the next language switch.
+    fn set_page_seg_mode(&mut self, mode: PageSegMode) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_variable("tessedit_pageseg_mode", &mode.tesseract_value().to_string())?;
+        self.page_seg_mode = Some(mode);
+        Ok(())
+    }
+
+    /// Initializes the engine again if the trained data of `language` is not loaded yet, or
+    /// if the previous initialization failed.
+    fn set_language(&mut self, language: ServerLanguage) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_languages(&[language])
+    }
+
+    /// Initializes the engine again with the trained data of all `languages` combined, e.g.
+    /// `eng+kor`, unless exactly these are loaded already.
+    fn set_languages(
+        &mut self,
+        languages: &[ServerLanguage],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if languages.is_empty() || (languages == self.languages.as_slice() && self.initialized) {
+            return Ok(());
+        }
+        self.init(languages)
+    }
+
+    /// Reads a crop. Fails if the last initialization failed.
+    fn recognize(
+        &mut self,
+        grayscale: &Mat,
+    ) -> Result<Vec<OcrCandidate>, Box<dyn std::error::Error>> {
+        if !self.initialized {
+            return Err("Tesseract is not initialized, the last language switch failed".into());
+        }
+        set_frame(self.api, grayscale)?;
+        let raw: *mut c_char = unsafe { capi::TessBaseAPIGetUTF8Text(self.api) };
+        if raw.is_null() {
+            return Err("Tesseract returned no text".into());
+        }
+        let text: Result<String, std::str::Utf8Error> =
+            unsafe { CStr::from_ptr(raw) }.to_str().map(String::from);
+        unsafe { capi::TessDeleteText(raw) };
+        let text: String = text?;
+        if text.trim().is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![OcrCandidate {
+            text,
+            confidence: unsafe { capi::TessBaseAPIMeanTextConf(self.api) } as f64 / 100.0,
+        }])
+    }
+}
+
+/// Hands the pixel buffer of a single channel `Mat` to Tesseract as a raw frame.
+///
+/// Tesseract copies the buffer, so no intermediate image format or Leptonica `Pix` is
+/// needed. Crops taken with `roi` share the rows of their parent image and are copied into a
+/// continuous buffer first.
+///
+/// # Errors
+/// Returns an error if the image is not 8-bit single channel or empty.
+fn set_frame(api: *mut capi::TessBaseAPI, image: &Mat) -> Result<(), Box<dyn std::error::Error>> {
+    if image.typ() != CV_8UC1 {
+        return Err("Tesseract frames must be 8-bit single channel images".into());
+    }
+    if image.empty() {
+        return Err("Tesseract frames must not be empty".into());
+    }
+    let copy: Mat;
+    let continuous: &Mat = if image.is_continuous() {
+        image
+    } else {
+        copy = image.try_clone()?;
+        &copy
+    };
+    let data: &[u8] = continuous.data_bytes()?;
+    unsafe {
+        capi::TessBaseAPISetImage(
+            api,
+            data.as_ptr(),
+            continuous.cols(),
+            continuous.rows(),
+            1,
+            continuous.cols(),
+        );
+        capi::TessBaseAPISetSourceResolution(api, SOURCE_RESOLUTION);
+    }
+    Ok(())
+}
+
+/// The user words and patterns of every server language, written once per process.
+///
+/// Every `TesseractBackend` of a run shares one `Vocabularies` and only reads the files, so
+/// the files are complete before any engine is initialized. The directory is removed when the
+/// last backend is dropped.
+#[derive(Debug)]
+pub struct Vocabularies {
+    dir: PathBuf,
+    variables: Vec<(ServerLanguage, Vec<(String, String)>)>,
+}
+
+impl Vocabularies {
+    /// Writes the tag strings of every language with `write_vocabulary` into a subdirectory
+    /// of `dir` named after its trained data. `dir` should be private to the process, e.g.
+    /// `retag-<pid>` below the temporary directory.
+    ///
+    /// # Errors
+    /// Returns an error if a directory or file cannot be written.
+    pub fn write(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut variables: Vec<(ServerLanguage, Vec<(String, String)>)> = vec![];
+        for language in ServerLanguage::ALL {
+            let language_dir: PathBuf = dir.join(language.tessdata_language());
+            variables.push((language, write_vocabulary(&language_dir, language.tag_strings())?));
+        }
+        Ok(Vocabularies {
+            dir: dir.to_path_buf(),
+            variables,
+        })
+    }
+
+    /// Returns the `user_words_file` and `user_patterns_file` variables of `language`.
+    pub fn variables(&self, language: ServerLanguage) -> &[(String, String)] {
+        self.variables
+            .iter()
+            .find(|(l, _)| *l == language)
+            .map(|(_, variables)| variables.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+impl Drop for Vocabularies {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Writes a Tesseract user words file and a user patterns file for a vocabulary.
+///
+/// Tag buttons only ever show words of the vocabulary, so telling Tesseract about them
+/// steers it away from dictionary words that merely look similar.
+///
+/// # Parameters
+/// - `dir`: The directory the files are written to. It is created if necessary.
+/// - `vocabulary`: The words to expect, e.g. the tag strings.
+///
+/// # Returns
+/// - `Result<Vec<(String, String)>, Box<dyn std::error::Error>>`: The `user_words_file` and
+///   `user_patterns_file` variables pointing to the written files, ready to be passed to
+///   `TessBaseAPIInit4`.
+///
+/// # Processing Steps
+/// 1. **Words**: Every vocabulary entry is written as a word. Hyphenated entries are also
+///    split into their parts, because Tesseract may read the hyphen as a word break.
+///
+/// 2. **Patterns**: Every entry is turned into a pattern of character classes: `\A` for an
+///    upper case letter, `\a` for a run of lower case letters and `\d` for a digit. Other
+///    characters are kept as they are.
+///
+/// # Errors
+/// Returns an error if the directory or the files cannot be written.
+pub fn write_vocabulary(
+    dir: &Path,
+    vocabulary: &[&str],
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let mut words: Vec<String> = vec![];
+    let mut patterns: Vec<String> = vec![];
+    for entry in vocabulary {
+        words.push(entry.to_string());
+        if entry.contains('-') {
+            words.extend(entry.split('-').map(String::from));
+        }
+        let mut pattern: String = String::new();
+        for c in entry.chars() {
+            if c.is_uppercase() {
+                pattern.push_str("\\A");
+            } else if c.is_lowercase() {
+                if !pattern.ends_with("\\a\\*") {
+                    pattern.push_str("\\a\\*");
+                }
+            } else if c.is_ascii_digit() {
+                pattern.push_str("\\d");
+            } else {
+                pattern.push(c);
+            }
+        }
+        patterns.push(pattern);
+    }
+    words.sort();
+    words.dedup();
+    patterns.sort();
+    patterns.dedup();
+
+    fs::create_dir_all(dir)?;
+    let words_file: PathBuf = dir.join("retag.user-words");
+    let patterns_file: PathBuf = dir.join("retag.user-patterns");
+    fs::write(&words_file, words.join("\n") + "\n")?;
+    fs::write(&patterns_file, patterns.join("\n") + "\n")?;
+    Ok(vec![
+        (
+            "user_words_file".into(),
+            words_file.to_string_lossy().to_string(),
+        ),
+        (
+            "user_patterns_file".into(),
+            patterns_file.to_string_lossy().to_string(),
+        ),
+    ])
+}
+
+#[test]
+fn vocabularies_are_written_once_and_removed_with_the_last_owner(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("retag-vocabularies-{}", std::process::id()));
+    let vocabularies: Arc<Vocabularies> = Arc::new(Vocabularies::write(&dir)?);
+    for language in ServerLanguage::ALL {
+        let variables: &[(String, String)] = vocabularies.variables(language);
+        assert_eq!(variables.len(), 2);
+        for (_, file) in variables {
+            assert!(Path::new(file).starts_with(dir.join(language.tessdata_language())));
+            assert!(!fs::read_to_string(file)?.is_empty());
+        }
+    }
+    let shared: Arc<Vocabularies> = Arc::clone(&vocabularies);
+    drop(vocabularies);
+    assert!(dir.exists());
+    drop(shared);
+    assert!(!dir.exists());
+    Ok(())
+}
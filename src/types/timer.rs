@@ -0,0 +1,137 @@
+use super::ocr::{self, OcrBackend};
+use opencv::{
+    core::{Mat, Rect},
+    imgproc,
+    prelude::MatTraitConst,
+};
+use std::fmt;
+
+/// Regions of the hour and minute digits on the tag selection screen as
+/// `(x, y, width, height)` fractions of the screen.
+static TIMER_HOURS_ROI: (f64, f64, f64, f64) = (0.330, 0.265, 0.075, 0.085);
+static TIMER_MINUTES_ROI: (f64, f64, f64, f64) = (0.455, 0.265, 0.075, 0.085);
+static TIMER_DIGIT_THRESH: f64 = 150.0;
+
+/// The recruitment time configured on the tag selection screen.
+///
+/// The game allows 1:00 to 9:00 in steps of ten minutes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecruitTime {
+    pub(crate) hours: u8,
+    pub(crate) minutes: u8,
+}
+
+impl RecruitTime {
+    pub const MIN: RecruitTime = RecruitTime {
+        hours: 1,
+        minutes: 0,
+    };
+    pub const MAX: RecruitTime = RecruitTime {
+        hours: 9,
+        minutes: 0,
+    };
+
+    /// Creates a `RecruitTime`, returning `None` if the value cannot be set in game.
+    ///
+    /// # Example Usage
+    /// ```rust
+    /// assert!(RecruitTime::new(7, 40).is_some());
+    /// assert!(RecruitTime::new(9, 10).is_none());
+    /// ```
+    pub fn new(hours: u8, minutes: u8) -> Option<Self> {
+        let time: RecruitTime = RecruitTime { hours, minutes };
+        if minutes % 10 != 0 || minutes >= 60 || time < Self::MIN || time > Self::MAX {
+            return None;
+        }
+        Some(time)
+    }
+
+    /// Returns the hour part of the recruitment time.
+    pub fn hours(&self) -> u8 {
+        self.hours
+    }
+
+    /// Returns the minute part of the recruitment time.
+    pub fn minutes(&self) -> u8 {
+        self.minutes
+    }
+}
+
+impl fmt::Display for RecruitTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hours, self.minutes)
+    }
+}
+
+/// Reads the configured recruitment time from a screenshot of the tag selection screen.
+///
+/// # Parameters
+/// - `image`: A reference to the full screenshot in BGR color format (e.g., CV_8UC3).
+/// - `backend`: The OCR backend used for recognition. The character whitelist is
+///   overwritten with digits only.
+///
+/// # Returns
+/// - `Result<Option<RecruitTime>, Box<dyn std::error::Error>>`:
+///   - `Some(time)` if both digit fields were read and form a valid recruitment time.
+///   - `None` if either field is unreadable or the value is out of range.
+///
+/// # Processing Steps
+/// 1. **Color Conversion**: The screenshot is converted to grayscale.
+///
+/// 2. **Digit Extraction**: The hour and minute fields are read separately from
+///    `TIMER_HOURS_ROI` and `TIMER_MINUTES_ROI` with a digits-only whitelist.
+///
+/// 3. **Validation**: The values are passed to `RecruitTime::new`, which rejects times the
+///    game does not allow.
+///
+/// # Example Usage
+/// ```rust
+/// let image: Mat = ...; // Load a screenshot of the tag selection screen
+/// let mut backend: TesseractBackend = ...; // Initialize the OCR backend
+/// if let Some(time) = read_recruit_time(&image, &mut backend)? {
+///     println!("Recruitment time: {}", time);
+/// }
+/// ```
+///
+/// # Errors
+/// This function may return errors related to image processing operations or OCR processing.
+pub fn read_recruit_time(
+    image: &Mat,
+    backend: &mut dyn OcrBackend,
+) -> Result<Option<RecruitTime>, Box<dyn std::error::Error>> {
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &image,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        opencv::core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    backend.set_whitelist(ocr::DIGIT_WHITELIST)?;
+
+    let hours_rect: Rect = ocr::relative_rect(gray.size()?, TIMER_HOURS_ROI);
+    let minutes_rect: Rect = ocr::relative_rect(gray.size()?, TIMER_MINUTES_ROI);
+    let hours: Option<u8> =
+        ocr::read_region(backend, &gray, &hours_rect, TIMER_DIGIT_THRESH, true)?
+            .parse::<u8>()
+            .ok();
+    let minutes: Option<u8> =
+        ocr::read_region(backend, &gray, &minutes_rect, TIMER_DIGIT_THRESH, true)?
+            .parse::<u8>()
+            .ok();
+    match (hours, minutes) {
+        (Some(hours), Some(minutes)) => Ok(RecruitTime::new(hours, minutes)),
+        _ => Ok(None),
+    }
+}
+
+#[test]
+fn recruit_time_accepts_only_settable_values() {
+    assert_eq!(RecruitTime::new(1, 0), Some(RecruitTime::MIN));
+    assert_eq!(RecruitTime::new(9, 0), Some(RecruitTime::MAX));
+    assert!(RecruitTime::new(7, 40).is_some());
+    assert!(RecruitTime::new(0, 50).is_none());
+    assert!(RecruitTime::new(9, 10).is_none());
+    assert!(RecruitTime::new(3, 15).is_none());
+    assert!(RecruitTime::new(3, 60).is_none());
+}
@@ -0,0 +1,205 @@
+use super::{
+    config::Config,
+    language::ServerLanguage,
+    ocr::OcrBackend,
+    scan::{self, ScanResult},
+    tag::{Tag, TagType},
+};
+use chrono::TimeDelta;
+use opencv::{
+    core::{self, Mat, Size},
+    imgproc,
+    prelude::*,
+    videoio,
+};
+
+/// Size of the thumbnails compared by the near-duplicate check.
+static THUMBNAIL_SIZE: (i32, i32) = (64, 36);
+static VIDEO_EXTENSIONS: [&str; 6] = ["mp4", "mkv", "avi", "mov", "webm", "m4v"];
+
+/// A recognition result taken from one frame of a video or frame sequence.
+///
+/// # Fields
+/// - `frame_index`: The zero based index of the frame in the source.
+/// - `timestamp`: The position of the frame in the source, if the frame rate is known.
+/// - `result`: The scan of the frame.
+#[derive(Debug)]
+pub struct FrameScan {
+    pub frame_index: usize,
+    pub timestamp: Option<TimeDelta>,
+    pub result: ScanResult,
+}
+
+/// Returns whether `input` should be opened with `VideoCapture` instead of being read as a
+/// single image.
+///
+/// Video files are recognized by their extension, numbered frame sequences by a printf-style
+/// pattern such as `frames/%04d.png`. Other paths that merely contain `%` are images.
+pub fn is_frame_source(input: &str) -> bool {
+    let lower: String = input.to_lowercase();
+    has_frame_pattern(input)
+        || VIDEO_EXTENSIONS
+            .iter()
+            .any(|extension: &&str| lower.ends_with(&format!(".{}", extension)))
+}
+
+/// Returns whether `input` contains a frame number placeholder, `%d` or `%0Nd`.
+fn has_frame_pattern(input: &str) -> bool {
+    input.match_indices('%').any(|(index, _): (usize, &str)| {
+        let rest: &str = &input[index + 1..];
+        let width: &str = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+        width.starts_with('d') && (width.len() == rest.len() || rest.starts_with('0'))
+    })
+}
+
+/// Scans a video file or a numbered frame sequence and reports every new tag roll and every
+/// recruitment result.
+///
+/// # Parameters
+/// - `source`: A video file path or a printf-style frame sequence pattern
+///   (e.g., `frames/%04d.png`).
+/// - `backend`: The OCR backend used for recognition.
+/// - `config`: The active settings. `video_sample_rate` sets how many frames per second of
+///   footage are inspected, `frame_diff_thresh` how much a frame must differ from the last
+///   inspected one to be scanned.
+/// - `emit`: Called for every frame whose recognized tag set differs from the last one
+///   emitted, and for every frame showing a different recruited operator than the last one.
+///
+/// # Processing Steps
+/// 1. **Sampling**: Frames are grabbed sequentially, and only every n-th frame is decoded so
+///    that `video_sample_rate` frames per second are inspected. Frame sequences carry no frame
+///    rate, so every frame is inspected.
+///
+/// 2. **Near-Duplicate Check**: Each inspected frame is reduced to a small grayscale thumbnail
+///    and compared with the previous one. If the mean absolute difference is below
+///    `frame_diff_thresh`, the frame is skipped without running OCR.
+///
+/// 3. **Scanning**: Changed frames are scanned like screenshots. The server language is
+///    detected on the first tag selection frame that allows it and kept for the rest of the
+///    source, see `scan::scan_image_with_detection`.
+///
+/// 4. **Change Detection**: A tag selection result is emitted only if it contains tags and its
+///    tag set differs from the last emitted one. Selecting or deselecting tags is not a
+///    change. A recruitment result is emitted only if its operator differs from the last
+///    emitted one.
+///
+/// # Example Usage
+/// ```rust
+/// scan_frames("session.mp4", &mut backend, &config, |scan: FrameScan| {
+///     println!("frame {}:\n{}", scan.frame_index, scan.result);
+/// })?;
+/// ```
+///
+/// # Errors
+/// Returns an error if the source cannot be opened, or if decoding or recognition fails.
+pub fn scan_frames(
+    source: &str,
+    backend: &mut dyn OcrBackend,
+    config: &Config,
+    mut emit: impl FnMut(FrameScan),
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut capture: videoio::VideoCapture = videoio::VideoCapture::from_file(source, videoio::CAP_ANY)?;
+    if !capture.is_opened()? {
+        return Err(format!("{}: could not open video or frame sequence", source).into());
+    }
+    let fps: f64 = capture.get(videoio::CAP_PROP_FPS)?;
+    let step: usize = if fps > 0.0 && config.video_sample_rate > 0.0 {
+        ((fps / config.video_sample_rate).round() as usize).max(1)
+    } else {
+        1
+    };
+
+    let mut frame: Mat = Mat::default();
+    let mut last_thumbnail: Option<Mat> = None;
+    let mut last_tags: Vec<TagType> = vec![];
+    let mut last_operator: Option<String> = None;
+    let mut detected_language: Option<ServerLanguage> = None;
+    let mut frame_index: usize = 0;
+    while capture.grab()? {
+        let index: usize = frame_index;
+        frame_index += 1;
+        if index % step != 0 || !capture.retrieve_def(&mut frame)? || frame.empty() {
+            continue;
+        }
+
+        let current: Mat = thumbnail(&frame)?;
+        if let Some(previous) = &last_thumbnail {
+            if frame_difference(previous, &current)? < config.frame_diff_thresh {
+                continue;
+            }
+        }
+        last_thumbnail = Some(current);
+
+        let result: ScanResult =
+            scan::scan_image_with_detection(&frame, backend, config, &mut detected_language)?;
+        if let Some(recruited) = result.recruit_result() {
+            let operator: String = recruited.operator().unwrap_or(recruited.raw_name()).into();
+            if last_operator.as_ref() != Some(&operator) {
+                last_operator = Some(operator);
+                emit(FrameScan {
+                    frame_index: index,
+                    timestamp: frame_timestamp(index, fps),
+                    result,
+                });
+            }
+            continue;
+        }
+        let mut tags: Vec<TagType> = result.tags().iter().map(Tag::tag_type).collect();
+        tags.sort();
+        if tags.is_empty() || tags == last_tags {
+            continue;
+        }
+        last_tags = tags;
+        emit(FrameScan {
+            frame_index: index,
+            timestamp: frame_timestamp(index, fps),
+            result,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the position of a frame in the source, or `None` if the frame rate is unknown.
+fn frame_timestamp(index: usize, fps: f64) -> Option<TimeDelta> {
+    (fps > 0.0).then(|| TimeDelta::milliseconds((index as f64 / fps * 1000.0) as i64))
+}
+
+/// Reduces a frame to a small grayscale thumbnail for the near-duplicate check.
+fn thumbnail(frame: &Mat) -> Result<Mat, Box<dyn std::error::Error>> {
+    let mut small: Mat = Mat::default();
+    imgproc::resize(
+        frame,
+        &mut small,
+        Size::new(THUMBNAIL_SIZE.0, THUMBNAIL_SIZE.1),
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )?;
+    let mut gray: Mat = Mat::default();
+    imgproc::cvt_color(
+        &small,
+        &mut gray,
+        imgproc::COLOR_BGR2GRAY,
+        0,
+        core::AlgorithmHint::ALGO_HINT_ACCURATE,
+    )?;
+    Ok(gray)
+}
+
+/// Returns the mean absolute difference of two thumbnails, scaled to `[0, 1]`.
+fn frame_difference(a: &Mat, b: &Mat) -> Result<f64, Box<dyn std::error::Error>> {
+    let mut diff: Mat = Mat::default();
+    core::absdiff(a, b, &mut diff)?;
+    Ok(core::mean_def(&diff)?.get(0).unwrap() / 255.0)
+}
+
+#[test]
+fn frame_sequences_need_a_number_placeholder() {
+    assert!(is_frame_source("frames/%04d.png"));
+    assert!(is_frame_source("frames/%d.png"));
+    assert!(is_frame_source("session.MP4"));
+    assert!(!is_frame_source("screenshots/100%.png"));
+    assert!(!is_frame_source("shots/50%off.png"));
+    assert!(!is_frame_source("frames/%4d.png"));
+    assert!(!is_frame_source("frames/%s.png"));
+}